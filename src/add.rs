@@ -0,0 +1,347 @@
+use std::{
+    fmt::Display,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{
+    error::{SymlonkError, SymlonkResult},
+    link::{ConflictPolicy, CreateLinkOptions, LinkOptions},
+    lock::{LockFile, LockedLink, ParseLockFileError},
+    log::Logger,
+};
+
+#[derive(Debug)]
+pub enum AddError {
+    NotFound(PathBuf),
+    /// Symlinks are not adopted, they may already be managed
+    IsASymlink(PathBuf),
+    NotInDestinationDir {
+        path: PathBuf,
+        destination_dir: PathBuf,
+    },
+    AlreadyDeclared {
+        link_name: PathBuf,
+        config_file: PathBuf,
+    },
+    TargetExists(PathBuf),
+    EditConfig {
+        config_file: PathBuf,
+        error: toml_edit::TomlError,
+    },
+    /// `symlinks` is not a table, so the entry cannot be appended
+    SymlinksNotATable(PathBuf),
+}
+
+impl Display for AddError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddError::NotFound(path) => {
+                f.write_fmt(format_args!("file not found: {}", path.to_string_lossy()))
+            }
+            AddError::IsASymlink(path) => f.write_fmt(format_args!(
+                "{} is already a symlink",
+                path.to_string_lossy()
+            )),
+            AddError::NotInDestinationDir {
+                path,
+                destination_dir,
+            } => f.write_fmt(format_args!(
+                "{} is not inside the destination directory {}",
+                path.to_string_lossy(),
+                destination_dir.to_string_lossy()
+            )),
+            AddError::AlreadyDeclared {
+                link_name,
+                config_file,
+            } => f.write_fmt(format_args!(
+                "{} is already declared in {}",
+                link_name.to_string_lossy(),
+                config_file.to_string_lossy()
+            )),
+            AddError::TargetExists(target) => f.write_fmt(format_args!(
+                "{} already exists in the source directory",
+                target.to_string_lossy()
+            )),
+            AddError::EditConfig { config_file, error } => f.write_fmt(format_args!(
+                "could not edit config file {}: {}",
+                config_file.to_string_lossy(),
+                error
+            )),
+            AddError::SymlinksNotATable(config_file) => f.write_fmt(format_args!(
+                "could not edit config file {}: symlinks is not a table",
+                config_file.to_string_lossy()
+            )),
+        }
+    }
+}
+
+/// Symlink created by `add`
+#[derive(Debug, Serialize)]
+pub struct AddReport {
+    pub link_name: PathBuf,
+    pub link_target: PathBuf,
+    pub config_file: PathBuf,
+}
+
+/// Change made by `add`, undone in reverse order if a later step fails
+enum AddStep {
+    CreatedDir(PathBuf),
+    Moved {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    ConfigWritten {
+        config_file: PathBuf,
+        contents: String,
+    },
+    Linked(PathBuf),
+}
+
+/// Move `path` into the source directory of `config_file` as `name` (its file
+/// name by default), declare it in the config file, replace it with a symlink
+/// and record the symlink in the lock file. Every change is rolled back if a
+/// step fails.
+pub fn add(
+    path: &Path,
+    config_file: &Path,
+    name: Option<&Path>,
+    lock_file_path: &Path,
+) -> SymlonkResult<AddReport> {
+    let log = Logger::default();
+    let config = crate::config::parse_config_file(config_file)?;
+    let config_file = config_file.canonicalize()?;
+
+    let link_name = crate::path::join(std::env::current_dir()?.as_path(), path);
+    match std::fs::symlink_metadata(link_name.as_path()) {
+        Ok(metadata) if metadata.is_symlink() => return Err(AddError::IsASymlink(link_name).into()),
+        Ok(_) => {}
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return Err(AddError::NotFound(link_name).into())
+        }
+        Err(error) => return Err(error.into()),
+    }
+    let declared_name = link_name
+        .strip_prefix(config.config.destination_dir.as_path())
+        .map_err(|_| AddError::NotInDestinationDir {
+            path: link_name.clone(),
+            destination_dir: config.config.destination_dir.clone(),
+        })?
+        .to_path_buf();
+    if let Some(declaration) = config.absolute_symlinks.get(&link_name) {
+        return Err(AddError::AlreadyDeclared {
+            link_name,
+            config_file: declaration.provenance.config_file.clone(),
+        }
+        .into());
+    }
+
+    let declared_target = match name {
+        Some(name) => name.to_path_buf(),
+        None => declared_name
+            .file_name()
+            .map(PathBuf::from)
+            .unwrap_or(declared_name.clone()),
+    };
+    let link_target = crate::path::join(
+        config.config.source_dir.as_path(),
+        declared_target.as_path(),
+    );
+    if crate::path::path_exists(link_target.as_path())? {
+        return Err(AddError::TargetExists(link_target).into());
+    }
+
+    let mut lock_file = match crate::lock::parse_lock_file(lock_file_path) {
+        Ok(lock_file) => lock_file,
+        Err(ParseLockFileError::Io(error)) if error.kind() == io::ErrorKind::NotFound => {
+            log.info(format_args!(
+                "lock file not found: {}, a new lock file will be created",
+                lock_file_path.to_string_lossy()
+            ));
+            LockFile::new(lock_file_path.to_path_buf())
+        }
+        Err(error) => {
+            return Err(SymlonkError::ParseLockFile {
+                lock_file_path: lock_file_path.to_path_buf(),
+                error,
+            })
+        }
+    };
+
+    let mut steps = Vec::new();
+    let result = apply(
+        &mut steps,
+        &mut lock_file,
+        config_file.as_path(),
+        (link_name.as_path(), declared_name.as_path()),
+        (link_target.as_path(), declared_target.as_path()),
+        &LinkOptions {
+            relative: config.config.relative,
+            ..LinkOptions::default()
+        },
+    );
+    if let Err(error) = result {
+        log.error(format_args!("add failed, rolling back: {}", error));
+        rollback(steps);
+        return Err(error);
+    }
+
+    log.success(format_args!(
+        "add {} -> {} to {}",
+        link_name.to_string_lossy(),
+        link_target.to_string_lossy(),
+        config_file.to_string_lossy()
+    ));
+    Ok(AddReport {
+        link_name,
+        link_target,
+        config_file,
+    })
+}
+
+/// `link` and `target` are (absolute path, path declared in the config file)
+fn apply(
+    steps: &mut Vec<AddStep>,
+    lock_file: &mut LockFile,
+    config_file: &Path,
+    (link_name, declared_name): (&Path, &Path),
+    (link_target, declared_target): (&Path, &Path),
+    link_options: &LinkOptions,
+) -> SymlonkResult<()> {
+    let log = Logger::default();
+
+    if let Some(parent) = link_target.parent() {
+        let mut missing_dirs = Vec::new();
+        for ancestor in parent.ancestors() {
+            if crate::path::path_exists(ancestor)? {
+                break;
+            }
+            missing_dirs.push(ancestor);
+        }
+        for dir in missing_dirs.into_iter().rev() {
+            std::fs::create_dir(dir)?;
+            steps.push(AddStep::CreatedDir(dir.to_path_buf()));
+        }
+    }
+    move_path(link_name, link_target)?;
+    steps.push(AddStep::Moved {
+        from: link_name.to_path_buf(),
+        to: link_target.to_path_buf(),
+    });
+    log.success(format_args!(
+        "move {} to {}",
+        link_name.to_string_lossy(),
+        link_target.to_string_lossy()
+    ));
+
+    let contents = std::fs::read_to_string(config_file)?;
+    let edited = add_symlink_entry(
+        config_file,
+        contents.as_str(),
+        declared_name,
+        declared_target,
+    )?;
+    std::fs::write(config_file, edited)?;
+    steps.push(AddStep::ConfigWritten {
+        config_file: config_file.to_path_buf(),
+        contents,
+    });
+
+    crate::link::create_link(
+        link_name,
+        link_target,
+        link_options,
+        &mut CreateLinkOptions::new(Some(ConflictPolicy::Fail)),
+    )?;
+    steps.push(AddStep::Linked(link_name.to_path_buf()));
+
+    lock_file.set_symlink(
+        link_name,
+        LockedLink {
+            relative: link_options.relative,
+            declared_in: Some(config_file.to_path_buf()),
+            ..LockedLink::new(link_target.to_path_buf())
+        },
+    );
+    lock_file
+        .write()
+        .map_err(|error| SymlonkError::WriteLockFile {
+            lock_file_path: lock_file.file_path().to_path_buf(),
+            error,
+        })
+}
+
+/// Append `name = "target"` to the `[symlinks]` table, keeping the
+/// formatting and comments of the document
+fn add_symlink_entry(
+    config_file: &Path,
+    contents: &str,
+    declared_name: &Path,
+    declared_target: &Path,
+) -> Result<String, AddError> {
+    let mut document: toml_edit::DocumentMut =
+        contents.parse().map_err(|error| AddError::EditConfig {
+            config_file: config_file.to_path_buf(),
+            error,
+        })?;
+    let symlinks = document
+        .entry("symlinks")
+        .or_insert_with(toml_edit::table)
+        .as_table_like_mut()
+        .ok_or_else(|| AddError::SymlinksNotATable(config_file.to_path_buf()))?;
+    symlinks.insert(
+        declared_name.to_string_lossy().as_ref(),
+        toml_edit::value(declared_target.to_string_lossy().as_ref()),
+    );
+
+    Ok(document.to_string())
+}
+
+/// Rename, or copy then delete a file across file systems
+fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    match std::fs::rename(from, to) {
+        Err(error) if error.kind() == io::ErrorKind::CrossesDevices && from.is_file() => {
+            std::fs::copy(from, to)?;
+            std::fs::remove_file(from)
+        }
+        result => result,
+    }
+}
+
+fn rollback(steps: Vec<AddStep>) {
+    let log = Logger::default();
+
+    for step in steps.into_iter().rev() {
+        let (description, result) = match step {
+            AddStep::CreatedDir(dir) => (
+                format!("remove directory {}", dir.to_string_lossy()),
+                std::fs::remove_dir(dir.as_path()),
+            ),
+            AddStep::Linked(link_name) => (
+                format!("unlink {}", link_name.to_string_lossy()),
+                std::fs::remove_file(link_name.as_path()),
+            ),
+            AddStep::ConfigWritten {
+                config_file,
+                contents,
+            } => (
+                format!("restore {}", config_file.to_string_lossy()),
+                std::fs::write(config_file.as_path(), contents),
+            ),
+            AddStep::Moved { from, to } => (
+                format!(
+                    "move {} back to {}",
+                    to.to_string_lossy(),
+                    from.to_string_lossy()
+                ),
+                move_path(to.as_path(), from.as_path()),
+            ),
+        };
+        match result {
+            Ok(()) => log.info(format_args!("rollback: {}", description)),
+            Err(error) => log.error(format_args!("rollback: {}: {}", description, error)),
+        }
+    }
+}
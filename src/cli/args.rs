@@ -2,12 +2,21 @@ use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
 
-use crate::lock::LockFile;
+use crate::{journal::RecoverAction, link::ConflictPolicy, lock::LockPaths, output::OutputFormat};
 
 /// Symlink management tool that uses a lock file to track create symlinks
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct SymlonkArgs {
+    /// Output format, `json` prints one JSON document on stdout and logs to stderr
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
+    /// Seconds to wait for another symlonk using the same lock file, 0 to
+    /// fail immediately
+    #[arg(long, global = true, default_value_t = 10, value_name = "SECONDS")]
+    pub lock_timeout: u64,
+
     #[command(subcommand)]
     pub command: SymlonkCommand,
 }
@@ -18,19 +27,122 @@ pub enum SymlonkCommand {
     #[command(subcommand)]
     Create(SymlonkCreateSubcommand),
 
+    /// Create a root config file, its JSON schema and an empty lock file
+    Init {
+        /// Directory of the new files, created if missing
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+
+        /// Source directory of the root config, relative to it
+        #[arg(long, default_value = ".")]
+        source_dir: PathBuf,
+
+        /// Destination directory of the root config, relative to it
+        #[arg(long, default_value = "~")]
+        destination_dir: PathBuf,
+
+        /// Add the lock file to the .gitignore of the directory
+        #[arg(long, default_value_t = false)]
+        gitignore: bool,
+    },
+
+    /// Move a file into the source directory of a config file, declare it and
+    /// replace it with a symlink
+    Add {
+        /// File to adopt, inside the destination directory of the config file
+        #[arg()]
+        path: PathBuf,
+
+        /// Config file in which the symlink is declared
+        #[arg(short, long)]
+        config: PathBuf,
+
+        /// Path of the file in the source directory [default: its file name]
+        #[arg(long = "as")]
+        name: Option<PathBuf>,
+
+        /// Lock file path [default: lock_file from symlonk-project.toml, or symlonk-lock.toml]
+        #[arg(short, long)]
+        lock_file: Option<PathBuf>,
+    },
+
+    /// Replace a managed symlink with a copy of its target, and remove it from
+    /// its config file and the lock file
+    #[command(alias = "forget")]
+    Remove {
+        /// Symlink to replace
+        #[arg()]
+        path: PathBuf,
+
+        /// Config files searched for the symlink declaration [default: config
+        /// files from symlonk-project.toml]
+        #[arg(short, long)]
+        config_files: Option<Vec<PathBuf>>,
+
+        /// Lock file path [default: lock_file from symlonk-project.toml, or symlonk-lock.toml]
+        #[arg(short, long)]
+        lock_file: Option<PathBuf>,
+    },
+
     /// Verify that the lock file matches config, that all symlinks in the lock file are created, and that symlinks point to existing files
     Verify {
         #[arg(short, long)]
         config_files: Option<Vec<PathBuf>>,
 
-        #[arg(default_value = LockFile::DEFAULT_LOCK_FILE_PATH)]
-        lock_file: PathBuf,
+        /// Let a later config file override a symlink declared with another
+        /// target in an earlier one, instead of failing
+        #[arg(long, default_value_t = false)]
+        allow_override: bool,
+
+        /// Lock file path [default: lock_file from symlonk-project.toml, or symlonk-lock.toml]
+        #[arg()]
+        lock_file: Option<PathBuf>,
+    },
+
+    /// Show the state of every symlink in the lock file and config
+    Status {
+        #[arg(short, long)]
+        config_files: Option<Vec<PathBuf>>,
+
+        /// Let a later config file override a symlink declared with another
+        /// target in an earlier one, instead of failing
+        #[arg(long, default_value_t = false)]
+        allow_override: bool,
+
+        /// Lock file path [default: lock_file from symlonk-project.toml, or symlonk-lock.toml]
+        #[arg()]
+        lock_file: Option<PathBuf>,
     },
 
-    /// Delete all created symlinks stored in the given lock file from the file system
+    /// Delete symlinks stored in the lock file from the file system, all of
+    /// them unless filtered
     Unlink {
-        #[arg(default_value = LockFile::DEFAULT_LOCK_FILE_PATH)]
-        lock_file: PathBuf,
+        /// Only unlink these symlinks, or the symlinks inside these directories
+        #[arg()]
+        paths: Vec<PathBuf>,
+
+        /// Only unlink the symlinks declared in these config files
+        #[arg(short, long)]
+        config_files: Option<Vec<PathBuf>>,
+
+        /// Only unlink the symlinks whose path matches this glob pattern
+        #[arg(long)]
+        only: Option<glob::Pattern>,
+
+        /// Lock file path [default: lock_file from symlonk-project.toml, or symlonk-lock.toml]
+        #[arg(short, long)]
+        lock_file: Option<PathBuf>,
+    },
+
+    /// Finish or revert a `create links` that was interrupted, using the
+    /// journal next to the lock file
+    Recover {
+        #[arg(value_enum)]
+        action: RecoverAction,
+
+        /// Lock file path [default: lock_file from symlonk-project.toml, or symlonk-lock.toml]
+        #[arg(short, long)]
+        lock_file: Option<PathBuf>,
     },
 }
 
@@ -45,17 +157,32 @@ pub enum SymlonkCreateSubcommand {
         /// Path to which the symlink should point
         #[arg()]
         symlink_target: PathBuf,
+
+        /// What to do when a file already exists at the symlink path
+        /// [default: on_conflict from symlonk-project.toml, or prompt]
+        #[arg(long, value_enum)]
+        on_conflict: Option<ConflictPolicy>,
+
+        /// Point the symlink to a path relative to its parent directory
+        #[arg(short, long, default_value_t = false)]
+        relative: bool,
     },
 
     /// Create symlinks from symlink declaration files
     Links {
-        /// List of paths to symlink declaration files
-        #[arg(required = true)]
+        /// List of paths to symlink declaration files [default: config files
+        /// from symlonk-project.toml]
+        #[arg()]
         symlink_declarations: Vec<PathBuf>,
 
-        /// Path of a symlink declaration file
-        #[arg(short, long, default_value = LockFile::DEFAULT_LOCK_FILE_PATH)]
-        lock_file: PathBuf,
+        /// Also use every *symlonk.toml file under this directory, skipping
+        /// files ignored by .gitignore
+        #[arg(long, env = "SYMLONK_CONFIG_DIR")]
+        discover: Option<PathBuf>,
+
+        /// Lock file path [default: lock_file from symlonk-project.toml, or symlonk-lock.toml]
+        #[arg(short, long)]
+        lock_file: Option<PathBuf>,
 
         /// Delete symlinks that are in lock file but not in config
         #[arg(short, long, default_value_t = false)]
@@ -66,16 +193,34 @@ pub enum SymlonkCreateSubcommand {
         #[arg(short, long, default_value_t = false)]
         verify: bool,
 
-        // TODO: add argument --dry-run ?
+        /// Print every action that would be taken without modifying the
+        /// file system or the lock file
+        #[arg(short = 'n', long, default_value_t = false)]
+        dry_run: bool,
+
+        /// Fail if applying the config would change the lock file, without
+        /// modifying the file system or the lock file
+        #[arg(long, default_value_t = false, conflicts_with = "dry_run")]
+        check: bool,
+
+        /// How to write paths in the lock file, kept for later runs
+        /// [default: as in the lock file, or absolute]
+        #[arg(long, value_enum)]
+        lock_paths: Option<LockPaths>,
+
+        /// What to do when a file already exists at a symlink path
+        /// [default: on_conflict from symlonk-project.toml, or prompt]
+        #[arg(long, value_enum)]
+        on_conflict: Option<ConflictPolicy>,
+
+        /// Create the lock file without prompting if it does not exist
+        #[arg(short, long, default_value_t = false)]
+        yes: bool,
 
-        // #[arg(short, long, default_value_t = false)]
-        // overwrite: bool,
-        //
-        // #[arg(short, long, default_value_t = false)]
-        // backup: bool,
-        //
-        // #[arg(short, long, default_value_t = false)]
-        // skip: bool,
+        /// Let a later config file override a symlink declared with another
+        /// target in an earlier one, instead of failing
+        #[arg(long, default_value_t = false)]
+        allow_override: bool,
     },
 
     /// Generate a JSON schema for symlonk configuration files
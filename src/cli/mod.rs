@@ -1,105 +1,139 @@
 mod args;
 
-use std::path::Path;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use clap::Parser;
+use clap::{CommandFactory, Parser};
 
 use crate::{
-    config,
-    link::{self, CreateLinkOptions},
-    lock::LockFile,
+    config::{self, SymlinkDeclaration},
+    discover,
+    error::{SymlonkError, SymlonkResult},
+    flock::LockFileGuard,
+    init::InitOptions,
+    journal::{Journal, JournalStep},
+    link::{self, CreateLinkError, CreateLinkOptions, CreateLinkOutcome, LinkOptions},
+    lock::{LockFile, LockedLink, ParseLockFileError},
     log::Logger,
+    manifest::Manifest,
+    output::{ApplyReport, ApplyVerifyReport, BackedUpLink, FailedLink, LinkEntry, OutputFormat},
+    plan::Plan,
     schema,
+    status::StatusReport,
+    unlink::UnlinkFilter,
 };
 
 use self::args::{SymlonkArgs, SymlonkCommand, SymlonkCreateSubcommand};
 
-pub fn run() {
+pub fn run() -> SymlonkResult<()> {
     let args = SymlonkArgs::parse();
+    let output = args.output;
+    let lock_timeout = Duration::from_secs(args.lock_timeout);
+    crate::log::log_to_stderr(output == OutputFormat::Json);
+    let manifest = match args.command {
+        SymlonkCommand::Create(SymlonkCreateSubcommand::Schema) | SymlonkCommand::Init { .. } => {
+            Manifest::default()
+        }
+        _ => Manifest::find()?.unwrap_or_default(),
+    };
+    if let Some(log_level) = manifest.log_level {
+        crate::log::set_log_level(log_level);
+    }
 
     match args.command {
         SymlonkCommand::Create(SymlonkCreateSubcommand::Link {
             symlink_name,
             symlink_target,
+            on_conflict,
+            relative,
         }) => {
             // println!("Hello, {:?} -> {:?}", symlink_name, symlink_target);
 
-            let mut create_link_opts = CreateLinkOptions::new(false, false, false);
+            let (symlink_name, symlink_target) = if relative {
+                // the relative target is computed from absolute paths
+                let current_dir = std::env::current_dir()?;
+                (
+                    crate::path::join(current_dir.as_path(), symlink_name.as_path()),
+                    crate::path::join(current_dir.as_path(), symlink_target.as_path()),
+                )
+            } else {
+                (symlink_name, symlink_target)
+            };
+            let mut create_link_opts = CreateLinkOptions::new(on_conflict.or(manifest.on_conflict));
             link::create_link(
                 symlink_name.as_path(),
                 symlink_target.as_path(),
+                &LinkOptions {
+                    relative,
+                    ..LinkOptions::default()
+                },
                 &mut create_link_opts,
-            )
-            .expect("create symlink");
+            )?;
         }
 
         SymlonkCommand::Create(SymlonkCreateSubcommand::Links {
             symlink_declarations,
+            discover,
             lock_file: lock_file_path,
             prune,
             verify,
+            dry_run,
+            check,
+            lock_paths,
+            on_conflict,
+            yes,
+            allow_override,
         }) => {
             let log = Logger::default();
-            let symlinks = config::parse_symlinks_from_config_files(&symlink_declarations)
-                .unwrap_or_else(|error| {
-                    log.error(format_args!(
-                        "[parse_symlinks_from_config_files] {:?}",
-                        error
-                    ));
-                    panic!()
-                });
-            let mut lock_file = crate::lock::parse_lock_file(lock_file_path.as_path())
-                .unwrap_or_else(|error| {
-                    log.debug(format_args!(
-                        "lock file {} could not be parsed: {:?}",
-                        lock_file_path.to_string_lossy(),
-                        error
-                    ));
-                    match error {
-                        crate::lock::ParseLockFileError::Deserialize(deserialize_error) => {
-                            log.error(format_args!(
-                                "invalid lock file ({}): {:?}",
-                                lock_file_path.to_string_lossy(),
-                                deserialize_error
-                            ));
-
-                            // TODO: graceful exit, don't panic
-                            panic!();
-                        }
-                        crate::lock::ParseLockFileError::Io(io_error) => {
-                            match io_error.kind() {
-                                std::io::ErrorKind::NotFound => {
-                                    let input_create_new_lock_file = log.prompt_char(
-                                        format_args!(
-                                            "lock file not found: {}. Create a new lock file? [y/N]",
-                                            lock_file_path.to_string_lossy(),
-                                        )
-                                    ).expect("prompt_char").is_some_and(|ch| ch.to_ascii_lowercase() == 'y');
-
-                                    if input_create_new_lock_file {
-                                        log.success(format_args!(
-                                            "create lock file {}",
-                                            lock_file_path.to_string_lossy()
-                                        ));
-                                        LockFile::new(lock_file_path.clone())
-                                    } else {
-                                        log.error(format_args!("no lock file, aborting"));
-                                        // TODO: graceful exit, don't panic
-                                        panic!();
-                                    }
-                                }
-                                _ => {
-                                    log.error(format_args!(
-                                        "IO error parsing lock file: {:?}",
-                                        io_error
-                                    ));
-                                    // TODO: graceful exit, don't panic
-                                    panic!();
-                                }
-                            }
-                        }
+            let lock_file_path = lock_file_path_or_default(lock_file_path, &manifest)?;
+            // read-only runs do not wait for a concurrent apply
+            let _guard = (!dry_run && !check)
+                .then(|| LockFileGuard::acquire(lock_file_path.as_path(), lock_timeout))
+                .transpose()?;
+            let on_conflict = on_conflict.or(manifest.on_conflict);
+            let symlink_declarations =
+                collect_config_files(symlink_declarations, discover.as_deref(), &manifest)?;
+            if symlink_declarations.is_empty() {
+                exit_missing_config_files(
+                    "pass them as arguments, use --discover or set config_files in symlonk-project.toml",
+                );
+            }
+            let declarations =
+                config::parse_symlink_declarations(&symlink_declarations, allow_override)?;
+            let symlinks = config::symlink_targets(&declarations);
+            let mut lock_file = match crate::lock::parse_lock_file(lock_file_path.as_path()) {
+                Ok(lock_file) => lock_file,
+                Err(ParseLockFileError::Io(io_error))
+                    if io_error.kind() == std::io::ErrorKind::NotFound =>
+                {
+                    if dry_run || check {
+                        log.info(format_args!(
+                            "lock file not found: {}, a new lock file would be created",
+                            lock_file_path.to_string_lossy(),
+                        ));
+                        LockFile::new(lock_file_path.clone())
+                    } else if yes || prompt_create_lock_file(&log, lock_file_path.as_path())? {
+                        log.success(format_args!(
+                            "create lock file {}",
+                            lock_file_path.to_string_lossy()
+                        ));
+                        LockFile::new(lock_file_path.clone())
+                    } else {
+                        return Err(SymlonkError::ParseLockFile {
+                            lock_file_path,
+                            error: ParseLockFileError::Io(io_error),
+                        });
                     }
-                });
+                }
+                Err(error) => {
+                    return Err(SymlonkError::ParseLockFile {
+                        lock_file_path,
+                        error,
+                    })
+                }
+            };
 
             log.debug(format_args!(
                 "Parsed symlinks from all config files: {:#?}",
@@ -111,126 +145,521 @@ pub fn run() {
                 lock_file
             ));
 
+            if let Some(lock_paths) = lock_paths {
+                lock_file.set_paths(lock_paths);
+            }
+
+            if check {
+                if prune {
+                    for (name, _) in lock_file.get_symlinks_to_delete(&symlinks) {
+                        lock_file.remove_symlink(name.as_path());
+                    }
+                }
+                for (name, declaration) in &declarations {
+                    lock_file.set_symlink(name.as_path(), locked_link(declaration, None));
+                }
+
+                let lock_file_error = |error| SymlonkError::WriteLockFile {
+                    lock_file_path: lock_file_path.clone(),
+                    error,
+                };
+                if !lock_file.is_up_to_date().map_err(lock_file_error)? {
+                    return Err(SymlonkError::LockFileOutdated { lock_file_path });
+                }
+                log.success(format_args!(
+                    "lock file is up to date: {}",
+                    lock_file_path.to_string_lossy()
+                ));
+                return Ok(());
+            }
+
+            if dry_run {
+                let plan = Plan::new(&declarations, &lock_file, prune, on_conflict)?;
+                match output {
+                    OutputFormat::Text => plan.print(&log),
+                    OutputFormat::Json => crate::output::print_json(&plan)?,
+                }
+                return Ok(());
+            }
+
+            let mut report = ApplyReport::default();
+            let mut journal = Journal::begin(lock_file_path.as_path())?;
+            let mut create_link_options =
+                link::CreateLinkOptions::new(on_conflict).with_journal(&mut journal);
+            let mut create_error = None;
+
             if prune {
                 let symlinks_to_delete = lock_file.get_symlinks_to_delete(&symlinks);
 
                 if symlinks_to_delete.is_empty() {
                     log.info(format_args!("prune: lock file has no outdated symlinks"))
                 }
-                for (name, _target) in symlinks_to_delete {
-                    match std::fs::remove_file(name.as_path()) {
+                for (name, target) in symlinks_to_delete {
+                    match prune_symlink(name.as_path(), &mut create_link_options) {
                         Ok(()) => {
                             lock_file.remove_symlink(name.as_path());
-                            log.success(format_args!("prune: unlink {}", name.to_string_lossy()))
+                            log.success(format_args!("prune: unlink {}", name.to_string_lossy()));
+                            report.pruned.push(LinkEntry::new(name, target));
+                        }
+                        Err(error) => {
+                            log.error(format_args!("prune: {}", error));
+                            report.failed.push(FailedLink {
+                                link_name: name,
+                                error: error.to_string(),
+                            });
+                            create_error = Some(error);
+                            break;
                         }
-                        Err(error) => log.error(format_args!("prune: {}", error)),
                     }
                 }
             }
 
-            let mut create_link_options = link::CreateLinkOptions::new(false, false, false);
-            for (name, target) in symlinks {
-                let created =
-                    link::create_link(name.as_path(), target.as_path(), &mut create_link_options)
-                        .expect("create_link");
-                // if let Some(CreatedSymlink { name, target }) = created {
-                if created {
-                    let old_target = lock_file.set_symlink(name.as_path(), target.as_path());
-                    log.debug(format_args!(
-                        "added symlink to lock file: {} -> {}{}",
-                        name.to_string_lossy(),
-                        target.to_string_lossy(),
-                        // old_target.map(|target| target.to_string_lossy().to_string()).unwrap(),
-                        // old_target.map_or("".into(), |target| target.to_string_lossy().to_string()),
-                        old_target.map_or("".into(), |target| format!(
-                            " (was {})",
-                            target.to_string_lossy()
-                        )),
-                    ))
+            // the loop stops at the first error, as does pruning
+            if create_error.is_none() {
+                for (name, declaration) in declarations {
+                    let target = declaration.target.clone();
+                    let outcome = match link::create_link(
+                        name.as_path(),
+                        target.as_path(),
+                        &declaration.options,
+                        &mut create_link_options,
+                    ) {
+                        Ok(outcome) => outcome,
+                        Err(error) => {
+                            log.info(format_args!(
+                                "{} is declared in {}",
+                                name.to_string_lossy(),
+                                declaration.provenance.config_file.to_string_lossy()
+                            ));
+                            report.failed.push(FailedLink {
+                                link_name: name,
+                                error: error.to_string(),
+                            });
+                            create_error = Some(error);
+                            break;
+                        }
+                    };
+                    let created = outcome.is_linked();
+                    let backup_path = match &outcome {
+                        CreateLinkOutcome::BackedUp { backup_path } => Some(backup_path.clone()),
+                        _ => None,
+                    };
+                    match outcome {
+                        CreateLinkOutcome::AlreadyLinked => report
+                            .already_linked
+                            .push(LinkEntry::new(name.clone(), target.clone())),
+                        CreateLinkOutcome::Created => report
+                            .created
+                            .push(LinkEntry::new(name.clone(), target.clone())),
+                        CreateLinkOutcome::Overwritten => report
+                            .overwritten
+                            .push(LinkEntry::new(name.clone(), target.clone())),
+                        CreateLinkOutcome::BackedUp { backup_path } => {
+                            report.backed_up.push(BackedUpLink {
+                                link_name: name.clone(),
+                                link_target: target.clone(),
+                                backup_path,
+                            })
+                        }
+                        CreateLinkOutcome::Skipped => report
+                            .skipped
+                            .push(LinkEntry::new(name.clone(), target.clone())),
+                        CreateLinkOutcome::ParentNotADirectory(parent) => {
+                            report.failed.push(FailedLink {
+                                link_name: name.clone(),
+                                error: format!(
+                                    "symlink parent is not a directory: {}",
+                                    parent.to_string_lossy()
+                                ),
+                            })
+                        }
+                    }
+                    // if let Some(CreatedSymlink { name, target }) = created {
+                    if created {
+                        let old_target = lock_file
+                            .set_symlink(name.as_path(), locked_link(&declaration, backup_path));
+                        log.debug(format_args!(
+                            "added symlink to lock file: {} -> {}{}",
+                            name.to_string_lossy(),
+                            target.to_string_lossy(),
+                            // old_target.map(|target| target.to_string_lossy().to_string()).unwrap(),
+                            // old_target.map_or("".into(), |target| target.to_string_lossy().to_string()),
+                            old_target.map_or("".into(), |target| format!(
+                                " (was {})",
+                                target.to_string_lossy()
+                            )),
+                        ))
+                    }
                 }
             }
 
-            let serialized_lock_file = lock_file.to_string().expect("toml");
+            if let Some(error) = create_error {
+                let reverted = journal.step_count();
+                report.rolled_back = reverted > 0;
+                journal.rollback()?;
+                if output == OutputFormat::Json {
+                    crate::output::print_json(&report)?;
+                }
+                return Err(if reverted == 0 {
+                    SymlonkError::Link(error)
+                } else {
+                    SymlonkError::RolledBack { reverted, error }
+                });
+            }
 
-            std::fs::write(lock_file_path.as_path(), serialized_lock_file).expect("write");
+            if let Err(error) = write_lock_file(&mut lock_file) {
+                journal.rollback()?;
+                return Err(error);
+            }
+            journal.commit()?;
 
-            if verify {
-                crate::lock::verify(lock_file_path.as_path(), Some(symlink_declarations));
+            let verify_report = verify
+                .then(|| {
+                    crate::lock::verify_report(
+                        lock_file_path.as_path(),
+                        Some(symlink_declarations),
+                        allow_override,
+                    )
+                })
+                .transpose()?;
+            match output {
+                OutputFormat::Text => {
+                    if let Some(verify_report) = &verify_report {
+                        verify_report.print(&log);
+                    }
+                }
+                // one document for both reports
+                OutputFormat::Json => match &verify_report {
+                    Some(verify_report) => crate::output::print_json(&ApplyVerifyReport {
+                        apply: &report,
+                        verify: verify_report,
+                    })?,
+                    None => crate::output::print_json(&report)?,
+                },
+            }
+            if let Some(verify_report) = verify_report {
+                verify_report.check()?;
             }
         }
 
         SymlonkCommand::Create(SymlonkCreateSubcommand::Schema) => {
-            schema::to_writer(std::io::stdout()).expect("to_writer");
+            schema::to_writer(std::io::stdout()).map_err(std::io::Error::from)?;
+        }
+
+        SymlonkCommand::Init {
+            dir,
+            source_dir,
+            destination_dir,
+            gitignore,
+        } => {
+            let report = crate::init::init(
+                dir.as_path(),
+                &InitOptions {
+                    source_dir,
+                    destination_dir,
+                    gitignore,
+                },
+            )?;
+
+            if output == OutputFormat::Json {
+                crate::output::print_json(&report)?;
+            }
+        }
+
+        SymlonkCommand::Add {
+            path,
+            config,
+            name,
+            lock_file: lock_file_path,
+        } => {
+            let lock_file_path = lock_file_path_or_default(lock_file_path, &manifest)?;
+            let _guard = LockFileGuard::acquire(lock_file_path.as_path(), lock_timeout)?;
+            let report = crate::add::add(
+                path.as_path(),
+                config.as_path(),
+                name.as_deref(),
+                lock_file_path.as_path(),
+            )?;
+
+            if output == OutputFormat::Json {
+                crate::output::print_json(&report)?;
+            }
+        }
+
+        SymlonkCommand::Remove {
+            path,
+            config_files,
+            lock_file: lock_file_path,
+        } => {
+            let lock_file_path = lock_file_path_or_default(lock_file_path, &manifest)?;
+            let _guard = LockFileGuard::acquire(lock_file_path.as_path(), lock_timeout)?;
+            let Some(config_files) = config_files_or_default(config_files, &manifest)? else {
+                exit_missing_config_files(
+                    "use --config-files or set config_files in symlonk-project.toml",
+                );
+            };
+            let report =
+                crate::remove::remove(path.as_path(), &config_files, lock_file_path.as_path())?;
+
+            if output == OutputFormat::Json {
+                crate::output::print_json(&report)?;
+            }
         }
 
         SymlonkCommand::Verify {
             lock_file: lock_file_path,
             config_files,
+            allow_override,
         } => {
-            crate::lock::verify(lock_file_path.as_path(), config_files);
+            let lock_file_path = lock_file_path_or_default(lock_file_path, &manifest)?;
+            let config_files = config_files_or_default(config_files, &manifest)?;
+            crate::lock::verify(
+                lock_file_path.as_path(),
+                config_files,
+                allow_override,
+                output,
+            )?;
         }
 
-        SymlonkCommand::Unlink {
+        SymlonkCommand::Status {
             lock_file: lock_file_path,
+            config_files,
+            allow_override,
         } => {
             let log = Logger::default();
-            let mut lock_file =
-                crate::lock::parse_lock_file(lock_file_path.as_path()).unwrap_or_else(|error| {
-                    log.debug(format_args!(
-                        "lock file {} could not be parsed: {:?}",
-                        lock_file_path.to_string_lossy(),
-                        error
+            let lock_file_path = lock_file_path_or_default(lock_file_path, &manifest)?;
+            let config_files = config_files_or_default(config_files, &manifest)?;
+            let lock_file = match crate::lock::parse_lock_file(lock_file_path.as_path()) {
+                Ok(lock_file) => lock_file,
+                Err(ParseLockFileError::Io(io_error))
+                    if io_error.kind() == std::io::ErrorKind::NotFound
+                        && config_files.is_some() =>
+                {
+                    log.info(format_args!(
+                        "lock file not found: {}",
+                        lock_file_path.to_string_lossy()
                     ));
-                    match error {
-                        crate::lock::ParseLockFileError::Deserialize(deserialize_error) => {
-                            log.error(format_args!(
-                                "invalid lock file ({}): {:?}",
-                                lock_file_path.to_string_lossy(),
-                                deserialize_error
-                            ));
+                    LockFile::new(lock_file_path.clone())
+                }
+                Err(error) => {
+                    return Err(SymlonkError::ParseLockFile {
+                        lock_file_path,
+                        error,
+                    })
+                }
+            };
+            let config_symlinks = match config_files {
+                Some(config_files) => Some(config::parse_symlink_declarations(
+                    &config_files,
+                    allow_override,
+                )?),
+                None => None,
+            };
 
-                            // TODO: graceful exit, don't panic
-                            panic!();
-                        }
-                        crate::lock::ParseLockFileError::Io(io_error) => {
-                            match io_error.kind() {
-                                std::io::ErrorKind::NotFound => {
-                                    log.error(format_args!(
-                                        "lock file not found: {}",
-                                        lock_file_path.to_string_lossy()
-                                    ));
-                                    panic!();
-                                }
-                                _ => {
-                                    log.error(format_args!(
-                                        "IO error parsing lock file: {:?}",
-                                        io_error
-                                    ));
-                                    // TODO: graceful exit, don't panic
-                                    panic!();
-                                }
-                            }
-                        }
-                    }
-                });
+            let report = StatusReport::new(&lock_file, config_symlinks.as_ref())?;
+            match output {
+                OutputFormat::Text => report.print(&log),
+                OutputFormat::Json => crate::output::print_json(&report)?,
+            }
+        }
 
-            for symlink_name in lock_file.symlinks().keys() {
-                let log = Logger::default();
-                match std::fs::remove_file(symlink_name) {
-                    Ok(()) => {
-                        log.success(format_args!("unlink {}", symlink_name.to_string_lossy()))
+        SymlonkCommand::Unlink {
+            paths,
+            config_files,
+            only,
+            lock_file: lock_file_path,
+        } => {
+            let log = Logger::default();
+            let lock_file_path = lock_file_path_or_default(lock_file_path, &manifest)?;
+            let _guard = LockFileGuard::acquire(lock_file_path.as_path(), lock_timeout)?;
+            let mut lock_file =
+                crate::lock::parse_lock_file(lock_file_path.as_path()).map_err(|error| {
+                    SymlonkError::ParseLockFile {
+                        lock_file_path: lock_file_path.clone(),
+                        error,
                     }
-                    Err(error) => log.error(format_args!("remove_file: {}", error)),
-                }
+                })?;
+
+            let current_dir = std::env::current_dir()?;
+            let filter = UnlinkFilter {
+                paths: Some(paths).filter(|paths| !paths.is_empty()).map(|paths| {
+                    paths
+                        .iter()
+                        .map(|path| crate::path::join(current_dir.as_path(), path))
+                        .collect()
+                }),
+                declared: config_files
+                    .map(|config_files| config::parse_symlink_declarations(&config_files, true))
+                    .transpose()?
+                    .map(|declarations| declarations.into_keys().collect()),
+                pattern: only
+                    .map(|pattern| absolute_pattern(current_dir.as_path(), pattern))
+                    .transpose()?,
+            };
+            let report = crate::unlink::unlink(&mut lock_file, &filter);
+            log.info(format_args!(
+                "unlinked {} symlinks, {} already missing, {} failed, {} paths unmatched",
+                report.unlinked.len(),
+                report.missing.len(),
+                report.failed.len(),
+                report.unmatched.len()
+            ));
+
+            write_lock_file(&mut lock_file)?;
+
+            if output == OutputFormat::Json {
+                crate::output::print_json(&report)?;
             }
 
-            log.success(format_args!("deleted {} symlinks", lock_file.symlink_count()));
+            if !report.failed.is_empty() || !report.unmatched.is_empty() {
+                return Err(SymlonkError::Unlink {
+                    failed: report.failed.len(),
+                    unmatched: report.unmatched.len(),
+                });
+            }
+        }
+
+        SymlonkCommand::Recover {
+            action,
+            lock_file: lock_file_path,
+        } => {
+            let lock_file_path = lock_file_path_or_default(lock_file_path, &manifest)?;
+            let _guard = LockFileGuard::acquire(lock_file_path.as_path(), lock_timeout)?;
+            crate::journal::recover(lock_file_path.as_path(), action)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Prefix a relative glob pattern with the escaped current directory
+fn absolute_pattern(current_dir: &Path, pattern: glob::Pattern) -> SymlonkResult<glob::Pattern> {
+    if Path::new(pattern.as_str()).is_absolute() {
+        return Ok(pattern);
+    }
+
+    let absolute = format!(
+        "{}/{}",
+        glob::Pattern::escape(current_dir.to_string_lossy().as_ref()),
+        pattern.as_str()
+    );
+    Ok(glob::Pattern::new(absolute.as_str()).map_err(std::io::Error::other)?)
+}
+
+fn exit_missing_config_files(hint: &str) -> ! {
+    SymlonkArgs::command()
+        .error(
+            clap::error::ErrorKind::MissingRequiredArgument,
+            format!("no config files given, {}", hint),
+        )
+        .exit()
+}
 
-            lock_file.remove_symlinks();
-            let serialized = lock_file.to_string().expect("serialize");
-            std::fs::write(lock_file_path.as_path(), serialized).expect("write");
+/// `--lock-file`, or the lock file of the manifest, or the default lock file
+fn lock_file_path_or_default(
+    lock_file_path: Option<PathBuf>,
+    manifest: &Manifest,
+) -> SymlonkResult<PathBuf> {
+    Ok(match lock_file_path {
+        Some(lock_file_path) => lock_file_path,
+        None => manifest
+            .lock_file()?
+            .unwrap_or_else(|| PathBuf::from(LockFile::DEFAULT_LOCK_FILE_PATH)),
+    })
+}
+
+/// `--config-files`, or the config files of the manifest if it has any
+fn config_files_or_default(
+    config_files: Option<Vec<PathBuf>>,
+    manifest: &Manifest,
+) -> SymlonkResult<Option<Vec<PathBuf>>> {
+    Ok(match config_files {
+        Some(config_files) => Some(config_files),
+        None => Some(manifest.config_files()?).filter(|config_files| !config_files.is_empty()),
+    })
+}
+
+/// Config files given on the command line, followed by the ones discovered
+/// in `discover_dir`. Without either, the config files of the manifest.
+fn collect_config_files(
+    mut config_files: Vec<PathBuf>,
+    discover_dir: Option<&Path>,
+    manifest: &Manifest,
+) -> SymlonkResult<Vec<PathBuf>> {
+    if config_files.is_empty() && discover_dir.is_none() {
+        return Ok(manifest.config_files()?);
+    }
+
+    if let Some(discover_dir) = discover_dir {
+        for config_file in discover::discover_config_files(discover_dir)? {
+            if !config_files.contains(&config_file) {
+                config_files.push(config_file);
+            }
         }
     }
+
+    Ok(config_files)
+}
+
+fn prompt_create_lock_file(log: &Logger, lock_file_path: &Path) -> SymlonkResult<bool> {
+    if !crate::log::is_interactive() {
+        log.error(format_args!(
+            "stdin is not a terminal, use --yes to create a new lock file"
+        ));
+        return Ok(false);
+    }
+
+    let input = log.prompt_char(format_args!(
+        "lock file not found: {}. Create a new lock file? [y/N]",
+        lock_file_path.to_string_lossy(),
+    ))?;
+
+    Ok(input.is_some_and(|ch| ch.eq_ignore_ascii_case(&'y')))
+}
+
+fn locked_link(declaration: &SymlinkDeclaration, backup_path: Option<PathBuf>) -> LockedLink {
+    LockedLink {
+        relative: declaration.options.relative,
+        declared_in: Some(declaration.provenance.config_file.clone()),
+        backup_path,
+        ..LockedLink::new(declaration.target.clone())
+    }
+}
+
+/// Delete a symlink that is in the lock file but not in config anymore. A
+/// symlink that was already deleted is not an error.
+fn prune_symlink(name: &Path, options: &mut CreateLinkOptions) -> Result<(), CreateLinkError> {
+    let io_error = |error| CreateLinkError::Io {
+        path: name.to_path_buf(),
+        error,
+    };
+    let symlink_target = match name.read_link() {
+        Ok(symlink_target) => symlink_target,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(error) => return Err(io_error(error)),
+    };
+
+    if let Some(journal) = options.journal_mut() {
+        journal
+            .record(JournalStep::RemoveSymlink {
+                link_name: name.to_path_buf(),
+                symlink_target,
+            })
+            .map_err(|error| CreateLinkError::Io {
+                path: journal.path().to_path_buf(),
+                error,
+            })?;
+    }
+    std::fs::remove_file(name).map_err(io_error)
+}
+
+fn write_lock_file(lock_file: &mut LockFile) -> SymlonkResult<()> {
+    lock_file
+        .write()
+        .map_err(|error| SymlonkError::WriteLockFile {
+            lock_file_path: lock_file.file_path().to_path_buf(),
+            error,
+        })
 }
 
 // fn unlink(symlink_name: &Path, lock_file: &mut LockFile) {
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+/// Restricts a symlink or a whole config file to some hosts. Every given
+/// field must match.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Condition {
+    /// Glob pattern matched against the hostname, e.g. "build-*"
+    pub hostname: Option<String>,
+
+    /// Operating system, as in `std::env::consts::OS`, e.g. "linux" or "macos"
+    pub os: Option<String>,
+
+    /// CPU architecture, as in `std::env::consts::ARCH`, e.g. "x86_64"
+    pub arch: Option<String>,
+
+    /// "VAR" if the environment variable must be set, "VAR=value" if it must
+    /// have a given value
+    pub env: Option<String>,
+}
+
+impl Condition {
+    pub fn matches(&self) -> Result<bool, glob::PatternError> {
+        if let Some(hostname_pattern) = &self.hostname {
+            let hostname = gethostname::gethostname();
+            let pattern = glob::Pattern::new(hostname_pattern)?;
+            if !pattern.matches(hostname.to_string_lossy().as_ref()) {
+                return Ok(false);
+            }
+        }
+
+        if self
+            .os
+            .as_ref()
+            .is_some_and(|os| os != std::env::consts::OS)
+        {
+            return Ok(false);
+        }
+
+        if self
+            .arch
+            .as_ref()
+            .is_some_and(|arch| arch != std::env::consts::ARCH)
+        {
+            return Ok(false);
+        }
+
+        if let Some(env) = &self.env {
+            let matches_env = match env.split_once('=') {
+                Some((name, expected_value)) => {
+                    std::env::var_os(name).is_some_and(|value| value == expected_value)
+                }
+                None => std::env::var_os(env).is_some(),
+            };
+            if !matches_env {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
@@ -1,42 +1,188 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    fmt::Display,
     io,
     path::{Path, PathBuf},
 };
 
 use serde::{Deserialize, Serialize};
 
-use crate::log::Logger;
+use crate::{
+    condition::Condition,
+    link::{ConflictPolicy, LinkOptions},
+    log::Logger,
+    path::ExpandPathError,
+};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Config {
     pub source_dir: PathBuf,
     pub destination_dir: PathBuf,
+
+    /// Default for the per-symlink `relative` option
+    #[serde(default)]
+    pub relative: bool,
+}
+
+/// Config files a symlink declaration comes from
+#[derive(Clone, Debug, Serialize)]
+pub struct Provenance {
+    /// Config file that declares the symlink
+    pub config_file: PathBuf,
+
+    /// Config file, the declaring one or one of its ancestors, that sets
+    /// `source_dir`
+    pub source_dir_file: PathBuf,
+
+    /// Config file, the declaring one or one of its ancestors, that sets
+    /// `destination_dir`
+    pub destination_dir_file: PathBuf,
+}
+
+#[derive(Clone, Debug)]
+pub struct SymlinkDeclaration {
+    /// Absolute link target
+    pub target: PathBuf,
+
+    pub options: LinkOptions,
+
+    pub provenance: Provenance,
 }
 
 #[derive(Debug)]
 pub struct ExtendedConfig {
     pub config: Config,
 
-    /// link_name -> link_target
-    pub absolute_symlinks: HashMap<PathBuf, PathBuf>,
+    /// Canonical path of the config file
+    pub config_file: PathBuf,
+
+    /// Canonical paths of the config files whose symlinks this file includes
+    /// through `extends`
+    pub ancestors: HashSet<PathBuf>,
+
+    /// Config file that sets `config.source_dir`
+    pub source_dir_file: PathBuf,
+
+    /// Config file that sets `config.destination_dir`
+    pub destination_dir_file: PathBuf,
+
+    /// link_name -> declaration
+    pub absolute_symlinks: HashMap<PathBuf, SymlinkDeclaration>,
 }
 
+/// Child configs are tried first, so that a file with `extends` is never
+/// read as a root config that ignores its parent
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(untagged)]
 enum ConfigCore {
-    RootConfig(Config),
     ChildConfig {
-        extends: PathBuf,
+        extends: Extends,
         source_dir: Option<PathBuf>,
         destination_dir: Option<PathBuf>,
+        relative: Option<bool>,
     },
+    RootConfig(Config),
+}
+
+/// Parent config files. Symlinks of later parents override those of earlier
+/// ones, and config values are inherited from the last parent.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+enum Extends {
+    One(PathBuf),
+    Many(Vec<PathBuf>),
+}
+
+impl Extends {
+    fn into_paths(self) -> Vec<PathBuf> {
+        match self {
+            Extends::One(path) => vec![path],
+            Extends::Many(paths) => paths,
+        }
+    }
+}
+
+/// Value of a `[symlinks]` entry. Deserialized by hand rather than as an
+/// untagged enum, so that an invalid table reports the faulty field.
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+enum SymlinkEntry {
+    /// Target path, or glob pattern whose matches are linked inside the
+    /// symlink name directory
+    Target(PathBuf),
+    /// `false` removes the inherited symlinks at or under the symlink name
+    Remove,
+    Table(SymlinkTable),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct SymlinkTable {
+    target: PathBuf,
+
+    /// Link every file under the target directory inside the symlink name
+    /// directory, instead of linking the directory itself
+    #[serde(default)]
+    recursive: bool,
+
+    /// Point the symlink to a path relative to its parent directory
+    relative: Option<bool>,
+
+    /// Octal permissions set on the target, e.g. "0755"
+    mode: Option<String>,
+
+    on_conflict: Option<ConflictPolicy>,
+
+    /// Only create the symlink if the target exists
+    #[serde(default)]
+    if_exists: bool,
+
+    /// Ignore the symlink unless the condition matches
+    when: Option<Condition>,
+}
+
+impl<'de> Deserialize<'de> for SymlinkEntry {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SymlinkEntryVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for SymlinkEntryVisitor {
+            type Value = SymlinkEntry;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a target path, a table or false")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                Ok(SymlinkEntry::Target(PathBuf::from(value)))
+            }
+
+            fn visit_bool<E: serde::de::Error>(self, value: bool) -> Result<Self::Value, E> {
+                match value {
+                    false => Ok(SymlinkEntry::Remove),
+                    true => Err(E::invalid_value(serde::de::Unexpected::Bool(true), &self)),
+                }
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> Result<Self::Value, A::Error> {
+                SymlinkTable::deserialize(serde::de::value::MapAccessDeserializer::new(map))
+                    .map(SymlinkEntry::Table)
+            }
+        }
+
+        deserializer.deserialize_any(SymlinkEntryVisitor)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 struct ConfigFile {
     config: ConfigCore,
-    symlinks: Option<HashMap<PathBuf, PathBuf>>,
+    symlinks: Option<HashMap<PathBuf, SymlinkEntry>>,
+
+    /// Ignore every symlink of this file unless the condition matches
+    when: Option<Condition>,
 }
 
 #[derive(Debug)]
@@ -44,6 +190,35 @@ pub enum ParseConfigFileErrorKind {
     // NotFound { path: PathBuf, io_error: io::Error },
     IoError(io::Error),
     InvalidToml(toml::de::Error),
+    InvalidGlob {
+        pattern: String,
+        error: glob::PatternError,
+    },
+    InvalidMode {
+        link_name: PathBuf,
+        mode: String,
+    },
+    InvalidPath {
+        path: PathBuf,
+        error: ExpandPathError,
+    },
+    /// Config files of the `extends` chain, starting and ending with the same
+    /// file
+    ExtendsCycle(Vec<PathBuf>),
+    /// Neither the config file nor its parents set this config value
+    MissingConfigValue(&'static str),
+    /// Two config files declare the same symlink with different targets
+    ConflictingDeclaration {
+        link_name: PathBuf,
+        first: Box<SymlinkDeclaration>,
+        second: Box<SymlinkDeclaration>,
+    },
+    /// A symlink would be created inside the directory of another symlink
+    NestedSymlink {
+        link_name: PathBuf,
+        parent_link_name: PathBuf,
+        parent_config_file: PathBuf,
+    },
 }
 
 impl From<toml::de::Error> for ParseConfigFileErrorKind {
@@ -65,7 +240,7 @@ pub struct ParseConfigFileError {
 }
 
 impl ParseConfigFileError {
-    fn new<E: Into<ParseConfigFileErrorKind>>(error: E, file: &Path) -> Self {
+    pub fn new<E: Into<ParseConfigFileErrorKind>>(error: E, file: &Path) -> Self {
         Self {
             kind: error.into(),
             config_file: file.to_path_buf(),
@@ -73,24 +248,191 @@ impl ParseConfigFileError {
     }
 }
 
+impl Display for ParseConfigFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            ParseConfigFileErrorKind::IoError(error) => f.write_fmt(format_args!(
+                "could not read config file {}: {}",
+                self.config_file.to_string_lossy(),
+                error
+            )),
+            ParseConfigFileErrorKind::InvalidToml(error) => f.write_fmt(format_args!(
+                "invalid config file {}: {}",
+                self.config_file.to_string_lossy(),
+                error
+            )),
+            ParseConfigFileErrorKind::InvalidGlob { pattern, error } => f.write_fmt(format_args!(
+                "invalid glob pattern in config file {}: {}: {}",
+                self.config_file.to_string_lossy(),
+                pattern,
+                error
+            )),
+            ParseConfigFileErrorKind::InvalidMode { link_name, mode } => f.write_fmt(format_args!(
+                "invalid mode in config file {}: {} = {:?}, expected octal permissions like \"0755\"",
+                self.config_file.to_string_lossy(),
+                link_name.to_string_lossy(),
+                mode
+            )),
+            ParseConfigFileErrorKind::InvalidPath { path, error } => f.write_fmt(format_args!(
+                "could not expand path in config file {}: {}: {}",
+                self.config_file.to_string_lossy(),
+                path.to_string_lossy(),
+                error
+            )),
+            ParseConfigFileErrorKind::ExtendsCycle(cycle) => f.write_fmt(format_args!(
+                "extends cycle in config file {}: {}",
+                self.config_file.to_string_lossy(),
+                cycle
+                    .iter()
+                    .map(|path| path.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join(" -> ")
+            )),
+            ParseConfigFileErrorKind::MissingConfigValue(name) => f.write_fmt(format_args!(
+                "invalid config file {}: {} is not set and not inherited from extends",
+                self.config_file.to_string_lossy(),
+                name
+            )),
+            ParseConfigFileErrorKind::ConflictingDeclaration {
+                link_name,
+                first,
+                second,
+            } => f.write_fmt(format_args!(
+                "conflicting declarations of symlink {}: {} in {}, {} in {} (use --allow-override to let the later config file win)",
+                link_name.to_string_lossy(),
+                first.target.to_string_lossy(),
+                first.provenance.config_file.to_string_lossy(),
+                second.target.to_string_lossy(),
+                second.provenance.config_file.to_string_lossy()
+            )),
+            ParseConfigFileErrorKind::NestedSymlink {
+                link_name,
+                parent_link_name,
+                parent_config_file,
+            } => f.write_fmt(format_args!(
+                "symlink {} declared in {} is inside symlink {} declared in {}",
+                link_name.to_string_lossy(),
+                self.config_file.to_string_lossy(),
+                parent_link_name.to_string_lossy(),
+                parent_config_file.to_string_lossy()
+            )),
+        }
+    }
+}
+
 pub type ParseConfigFileResult<Res> = Result<Res, ParseConfigFileError>;
 
 pub fn parse_symlinks_from_config_files(
-    config_files: &Vec<PathBuf>,
+    config_files: &[PathBuf],
+    allow_override: bool,
 ) -> ParseConfigFileResult<HashMap<PathBuf, PathBuf>> {
-    let mut symlinks = HashMap::new();
+    let declarations = parse_symlink_declarations(config_files, allow_override)?;
+
+    Ok(symlink_targets(&declarations))
+}
+
+/// Symlinks declared in all config files. A symlink declared with different
+/// targets in two config files is an error, unless `allow_override` lets the
+/// later file win. A file that another one extends is skipped, its symlinks
+/// are already included, overridden or removed by the extending file.
+pub fn parse_symlink_declarations(
+    config_files: &[PathBuf],
+    allow_override: bool,
+) -> ParseConfigFileResult<HashMap<PathBuf, SymlinkDeclaration>> {
+    let log = Logger::default();
+    let mut symlinks: HashMap<PathBuf, SymlinkDeclaration> = HashMap::new();
 
-    for config_file in config_files {
-        let config = parse_config_file(config_file.as_path())?;
+    let configs = config_files
+        .iter()
+        .map(|config_file| parse_config_file(config_file.as_path()))
+        .collect::<ParseConfigFileResult<Vec<_>>>()?;
+    let extended_files: HashSet<PathBuf> = configs
+        .iter()
+        .flat_map(|config| config.ancestors.iter().cloned())
+        .collect();
 
-        for (name, target) in config.absolute_symlinks {
-            symlinks.insert(name, target);
+    for config in configs {
+        if extended_files.contains(&config.config_file) {
+            log.debug(format_args!(
+                "skip {}, its symlinks are included by a file that extends it",
+                config.config_file.to_string_lossy()
+            ));
+            continue;
+        }
+
+        let mut declarations: Vec<_> = config.absolute_symlinks.into_iter().collect();
+        declarations.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (name, declaration) in declarations {
+            match symlinks.get(&name) {
+                Some(previous) if previous.target != declaration.target => {
+                    if !allow_override {
+                        return Err(ParseConfigFileError::new(
+                            ParseConfigFileErrorKind::ConflictingDeclaration {
+                                link_name: name,
+                                first: Box::new(previous.clone()),
+                                second: Box::new(declaration.clone()),
+                            },
+                            declaration.provenance.config_file.as_path(),
+                        ));
+                    }
+                    log.info(format_args!(
+                        "override: {} -> {} from {} replaces {} from {}",
+                        name.to_string_lossy(),
+                        declaration.target.to_string_lossy(),
+                        declaration.provenance.config_file.to_string_lossy(),
+                        previous.target.to_string_lossy(),
+                        previous.provenance.config_file.to_string_lossy()
+                    ));
+                }
+                _ => {}
+            }
+            symlinks.insert(name, declaration);
         }
     }
 
+    check_nested_symlinks(&symlinks)?;
+
     Ok(symlinks)
 }
 
+/// A symlink inside the directory of another symlink would be created in the
+/// other symlink's target directory
+fn check_nested_symlinks(
+    symlinks: &HashMap<PathBuf, SymlinkDeclaration>,
+) -> ParseConfigFileResult<()> {
+    let mut link_names: Vec<_> = symlinks.keys().collect();
+    link_names.sort();
+
+    for link_name in link_names {
+        let parent_link = link_name
+            .ancestors()
+            .skip(1)
+            .find_map(|ancestor| symlinks.get_key_value(ancestor));
+        if let Some((parent_link_name, parent_declaration)) = parent_link {
+            return Err(ParseConfigFileError::new(
+                ParseConfigFileErrorKind::NestedSymlink {
+                    link_name: link_name.clone(),
+                    parent_link_name: parent_link_name.clone(),
+                    parent_config_file: parent_declaration.provenance.config_file.clone(),
+                },
+                symlinks[link_name].provenance.config_file.as_path(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// link_name -> link_target
+pub fn symlink_targets(
+    declarations: &HashMap<PathBuf, SymlinkDeclaration>,
+) -> HashMap<PathBuf, PathBuf> {
+    declarations
+        .iter()
+        .map(|(name, declaration)| (name.clone(), declaration.target.clone()))
+        .collect()
+}
+
 fn deserialize_config_file(
     file_contents: &str,
     file_path: &Path,
@@ -98,11 +440,28 @@ fn deserialize_config_file(
     toml::from_str(file_contents).map_err(|error| ParseConfigFileError::new(error, file_path))
 }
 
-fn parse_config_file(config_file: &Path) -> ParseConfigFileResult<ExtendedConfig> {
+pub fn parse_config_file(config_file: &Path) -> ParseConfigFileResult<ExtendedConfig> {
+    resolve_config_file(config_file, &mut Vec::new())
+}
+
+/// Parse `config_file` and its `extends` ancestors. `extends_chain` holds the
+/// canonical paths of the files that (transitively) extend `config_file`.
+fn resolve_config_file(
+    config_file: &Path,
+    extends_chain: &mut Vec<PathBuf>,
+) -> ParseConfigFileResult<ExtendedConfig> {
     let log = Logger::default();
     let config_file = config_file
         .canonicalize()
         .map_err(|error| ParseConfigFileError::new(error, config_file))?;
+    if let Some(cycle_start) = extends_chain.iter().position(|path| path == &config_file) {
+        let mut cycle = extends_chain[cycle_start..].to_vec();
+        cycle.push(config_file.clone());
+        return Err(ParseConfigFileError::new(
+            ParseConfigFileErrorKind::ExtendsCycle(cycle),
+            extends_chain[extends_chain.len() - 1].as_path(),
+        ));
+    }
     let config_file_dir = config_file
         .parent()
         .map(PathBuf::from)
@@ -111,59 +470,364 @@ fn parse_config_file(config_file: &Path) -> ParseConfigFileResult<ExtendedConfig
         .map_err(|error| ParseConfigFileError::new(error, config_file.as_path()))?;
     let parsed_file = deserialize_config_file(file_contents.as_str(), config_file.as_path())?;
 
-    let config = match parsed_file.config {
-        ConfigCore::RootConfig(root_config) => Config {
-            source_dir: crate::path::normalize_path(
-                config_file_dir
-                    .join(root_config.source_dir.as_path())
-                    .as_path(),
-            ),
-            destination_dir: crate::path::normalize_path(
-                config_file_dir
-                    .join(root_config.destination_dir.as_path())
-                    .as_path(),
-            ),
-        },
+    let mut ancestors = HashSet::new();
+
+    let (config, source_dir_file, destination_dir_file, inherited_symlinks) = match parsed_file
+        .config
+    {
+        ConfigCore::RootConfig(root_config) => (
+            Config {
+                source_dir: crate::path::join(
+                    config_file_dir.as_path(),
+                    expand_path(root_config.source_dir.as_path(), config_file.as_path())?.as_path(),
+                ),
+                destination_dir: crate::path::join(
+                    config_file_dir.as_path(),
+                    expand_path(root_config.destination_dir.as_path(), config_file.as_path())?
+                        .as_path(),
+                ),
+                relative: root_config.relative,
+            },
+            config_file.clone(),
+            config_file.clone(),
+            HashMap::new(),
+        ),
         ConfigCore::ChildConfig {
             extends,
             source_dir,
             destination_dir,
+            relative,
         } => {
-            let parent_path = config_file_dir.join(extends);
-            let parent_config = parse_config_file(parent_path.as_path())?;
-
-            Config {
-                source_dir: source_dir
+            extends_chain.push(config_file.clone());
+            let parent_configs = extends
+                .into_paths()
+                .into_iter()
+                .map(|parent_path| {
+                    let parent_path = config_file_dir
+                        .join(expand_path(parent_path.as_path(), config_file.as_path())?);
+                    resolve_config_file(parent_path.as_path(), extends_chain)
+                })
+                .collect::<ParseConfigFileResult<Vec<_>>>();
+            extends_chain.pop();
+            let mut parent_configs = parent_configs?;
+            let expand_dir = |dir: PathBuf| {
+                expand_path(dir.as_path(), config_file.as_path())
                     .map(|dir| crate::path::join(config_file_dir.as_path(), dir.as_path()))
-                    .unwrap_or(parent_config.config.source_dir.clone()),
-                destination_dir: destination_dir
-                    .map(|dir| crate::path::join(config_file_dir.as_path(), dir.as_path()))
-                    .unwrap_or(parent_config.config.destination_dir.clone()),
+            };
+            let missing_value = |name| {
+                ParseConfigFileError::new(
+                    ParseConfigFileErrorKind::MissingConfigValue(name),
+                    config_file.as_path(),
+                )
+            };
+
+            let mut inherited_symlinks = HashMap::new();
+            for parent_config in parent_configs.iter_mut() {
+                inherited_symlinks.extend(std::mem::take(&mut parent_config.absolute_symlinks));
+                ancestors.insert(parent_config.config_file.clone());
+                ancestors.extend(std::mem::take(&mut parent_config.ancestors));
             }
+            let last_parent = parent_configs.pop();
+
+            let (source_dir, source_dir_file) = match (source_dir, &last_parent) {
+                (Some(dir), _) => (expand_dir(dir)?, config_file.clone()),
+                (None, Some(parent)) => (
+                    parent.config.source_dir.clone(),
+                    parent.source_dir_file.clone(),
+                ),
+                (None, None) => return Err(missing_value("source_dir")),
+            };
+            let (destination_dir, destination_dir_file) = match (destination_dir, &last_parent) {
+                (Some(dir), _) => (expand_dir(dir)?, config_file.clone()),
+                (None, Some(parent)) => (
+                    parent.config.destination_dir.clone(),
+                    parent.destination_dir_file.clone(),
+                ),
+                (None, None) => return Err(missing_value("destination_dir")),
+            };
+
+            (
+                Config {
+                    source_dir,
+                    destination_dir,
+                    relative: relative
+                        .or(last_parent.map(|parent| parent.config.relative))
+                        .unwrap_or_default(),
+                },
+                source_dir_file,
+                destination_dir_file,
+                inherited_symlinks,
+            )
         }
     };
-    let absolute_symlinks = parsed_file
-        .symlinks
-        .unwrap_or_default()
-        .iter()
-        .map(|(link_name, link_target)| {
-            (
-                crate::path::join(config.destination_dir.as_path(), link_name),
-                crate::path::join(config.source_dir.as_path(), link_target),
+    let provenance = Provenance {
+        config_file: config_file.clone(),
+        source_dir_file: source_dir_file.clone(),
+        destination_dir_file: destination_dir_file.clone(),
+    };
+    // symlinks of this file override inherited ones
+    let mut absolute_symlinks = inherited_symlinks;
+    let file_symlinks = match &parsed_file.when {
+        // the whole file is excluded, with the symlinks it inherits
+        Some(condition) if !condition_matches(condition, config_file.as_path())? => {
+            log.debug(format_args!(
+                "skip symlinks of {}, condition does not match: {:?}",
+                config_file.to_string_lossy(),
+                condition
+            ));
+            absolute_symlinks.clear();
+            ancestors.clear();
+            HashMap::new()
+        }
+        _ => parsed_file.symlinks.unwrap_or_default(),
+    };
+    // `false` entries only remove inherited symlinks, so they are applied
+    // before any symlink of this file is added
+    for (link_name, entry) in &file_symlinks {
+        if let SymlinkEntry::Remove = entry {
+            let link_name = crate::path::join(
+                config.destination_dir.as_path(),
+                expand_path(link_name.as_path(), config_file.as_path())?.as_path(),
+            );
+            let inherited_count = absolute_symlinks.len();
+            absolute_symlinks.retain(|name, _| !name.starts_with(link_name.as_path()));
+            log.debug(format_args!(
+                "removed {} inherited symlinks at {}",
+                inherited_count - absolute_symlinks.len(),
+                link_name.to_string_lossy()
+            ));
+        }
+    }
+    for (link_name, entry) in file_symlinks {
+        let link_name = crate::path::join(
+            config.destination_dir.as_path(),
+            expand_path(link_name.as_path(), config_file.as_path())?.as_path(),
+        );
+        if let SymlinkEntry::Table(SymlinkTable {
+            when: Some(condition),
+            ..
+        }) = &entry
+        {
+            if !condition_matches(condition, config_file.as_path())? {
+                log.debug(format_args!(
+                    "skip {}, condition does not match: {:?}",
+                    link_name.to_string_lossy(),
+                    condition
+                ));
+                continue;
+            }
+        }
+
+        let (link_target, recursive, if_exists, options) = match entry {
+            SymlinkEntry::Remove => continue,
+            SymlinkEntry::Target(target) => {
+                let options = LinkOptions {
+                    relative: config.relative,
+                    ..LinkOptions::default()
+                };
+                (target, false, false, options)
+            }
+            SymlinkEntry::Table(SymlinkTable {
+                target,
+                recursive,
+                relative,
+                mode,
+                on_conflict,
+                if_exists,
+                when: _,
+            }) => {
+                let mode = mode
+                    .map(|mode| parse_mode(link_name.as_path(), mode))
+                    .transpose()
+                    .map_err(|error| ParseConfigFileError::new(error, config_file.as_path()))?;
+                let options = LinkOptions {
+                    relative: relative.unwrap_or(config.relative),
+                    mode,
+                    on_conflict,
+                };
+                (target, recursive, if_exists, options)
+            }
+        };
+        let link_target = expand_path(link_target.as_path(), config_file.as_path())?;
+
+        // only the entry is a pattern, source_dir may contain glob characters
+        if recursive || is_glob(link_target.as_path()) {
+            let (literal_prefix, mut glob_suffix) = split_glob(link_target.as_path());
+            if recursive {
+                glob_suffix = glob_suffix.join("**").join("*");
+            }
+            let base_dir = crate::path::join(config.source_dir.as_path(), literal_prefix.as_path());
+            let expanded = expand_glob(
+                link_name.as_path(),
+                base_dir.as_path(),
+                glob_suffix.as_path(),
             )
-        })
-        .collect();
+            .map_err(|error| ParseConfigFileError::new(error, config_file.as_path()))?;
+            log.debug(format_args!(
+                "expanded {} to {} symlinks",
+                base_dir.join(glob_suffix).to_string_lossy(),
+                expanded.len()
+            ));
+            absolute_symlinks.extend(expanded.into_iter().map(|(name, target)| {
+                (
+                    name,
+                    SymlinkDeclaration {
+                        target,
+                        options: options.clone(),
+                        provenance: provenance.clone(),
+                    },
+                )
+            }));
+            continue;
+        }
+
+        let link_target = crate::path::join(config.source_dir.as_path(), link_target.as_path());
+        if if_exists && !link_target.exists() {
+            log.debug(format_args!(
+                "skip {}, target does not exist: {}",
+                link_name.to_string_lossy(),
+                link_target.to_string_lossy()
+            ));
+        } else {
+            absolute_symlinks.insert(
+                link_name,
+                SymlinkDeclaration {
+                    target: link_target,
+                    options,
+                    provenance: provenance.clone(),
+                },
+            );
+        }
+    }
 
     let extended_config = ExtendedConfig {
         config,
+        config_file: config_file.clone(),
+        ancestors,
+        source_dir_file,
+        destination_dir_file,
         absolute_symlinks,
     };
-    log.debug(
-        format_args!(
-            "parsed config file {} : {:#?}",
-            config_file.to_string_lossy(),
-            extended_config
-        ),
-    );
+    log.debug(format_args!(
+        "parsed config file {} : {:#?}",
+        config_file.to_string_lossy(),
+        extended_config
+    ));
     Ok(extended_config)
 }
+
+fn expand_path(path: &Path, config_file: &Path) -> ParseConfigFileResult<PathBuf> {
+    crate::path::expand(path).map_err(|error| {
+        ParseConfigFileError::new(
+            ParseConfigFileErrorKind::InvalidPath {
+                path: path.to_path_buf(),
+                error,
+            },
+            config_file,
+        )
+    })
+}
+
+fn condition_matches(condition: &Condition, config_file: &Path) -> ParseConfigFileResult<bool> {
+    condition.matches().map_err(|error| {
+        ParseConfigFileError::new(
+            ParseConfigFileErrorKind::InvalidGlob {
+                pattern: condition.hostname.clone().unwrap_or_default(),
+                error,
+            },
+            config_file,
+        )
+    })
+}
+
+fn parse_mode(link_name: &Path, mode: String) -> Result<u32, ParseConfigFileErrorKind> {
+    match u32::from_str_radix(mode.trim_start_matches("0o"), 8) {
+        Ok(parsed_mode) if parsed_mode <= 0o7777 => Ok(parsed_mode),
+        _ => Err(ParseConfigFileErrorKind::InvalidMode {
+            link_name: link_name.to_path_buf(),
+            mode,
+        }),
+    }
+}
+
+fn is_glob(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '['])
+}
+
+/// Split a glob entry into its leading components without glob characters
+/// and the rest
+fn split_glob(entry: &Path) -> (PathBuf, PathBuf) {
+    let mut literal_prefix = PathBuf::new();
+    let mut components = entry.components().peekable();
+    while let Some(component) = components.next_if(|component| !is_glob(Path::new(component))) {
+        literal_prefix.push(component);
+    }
+    (literal_prefix, components.collect())
+}
+
+/// Link every file matching `glob_suffix` under `base_dir` inside `link_dir`,
+/// keeping its path relative to `base_dir`, which is matched literally
+fn expand_glob(
+    link_dir: &Path,
+    base_dir: &Path,
+    glob_suffix: &Path,
+) -> Result<HashMap<PathBuf, PathBuf>, ParseConfigFileErrorKind> {
+    let pattern_str = format!(
+        "{}/{}",
+        glob::Pattern::escape(base_dir.to_string_lossy().as_ref()),
+        glob_suffix.to_string_lossy()
+    );
+    let matches = glob::glob(pattern_str.as_str()).map_err(|error| {
+        ParseConfigFileErrorKind::InvalidGlob {
+            pattern: base_dir.join(glob_suffix).to_string_lossy().to_string(),
+            error,
+        }
+    })?;
+
+    let mut symlinks = HashMap::new();
+    for link_target in matches {
+        let link_target = link_target.map_err(io::Error::from)?;
+        if link_target.is_dir() {
+            continue;
+        }
+
+        if let Ok(relative_path) = link_target.strip_prefix(base_dir) {
+            symlinks.insert(link_dir.join(relative_path), link_target);
+        }
+    }
+
+    Ok(symlinks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_glob_entries() {
+        let split = |entry: &str| split_glob(Path::new(entry));
+
+        assert_eq!(
+            split("dir/*.lua"),
+            (PathBuf::from("dir"), PathBuf::from("*.lua"))
+        );
+        assert_eq!(split("*/x"), (PathBuf::new(), PathBuf::from("*/x")));
+        assert_eq!(
+            split("a/b[0]/c"),
+            (PathBuf::from("a"), PathBuf::from("b[0]/c"))
+        );
+        assert_eq!(split("a/b"), (PathBuf::from("a/b"), PathBuf::new()));
+    }
+
+    #[test]
+    fn parse_octal_modes() {
+        let parse = |mode: &str| parse_mode(Path::new("link"), mode.to_string()).ok();
+
+        assert_eq!(parse("0755"), Some(0o755));
+        assert_eq!(parse("0o644"), Some(0o644));
+        assert_eq!(parse("7777"), Some(0o7777));
+        assert_eq!(parse("17777"), None);
+        assert_eq!(parse("0758"), None);
+        assert_eq!(parse(""), None);
+    }
+}
@@ -0,0 +1,57 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::{ParseConfigFileError, ParseConfigFileResult},
+    log::Logger,
+};
+
+/// Suffix of the file names of discovered config files
+pub const CONFIG_FILE_SUFFIX: &str = "symlonk.toml";
+
+/// Every `*symlonk.toml` file under `dir`, sorted by path. Files ignored by
+/// `.gitignore`, `.ignore` or `.git/info/exclude` are skipped, hidden
+/// directories other than `.git` are searched. Finding no config file is an
+/// error, so that a mistyped directory cannot prune every symlink.
+pub fn discover_config_files(dir: &Path) -> ParseConfigFileResult<Vec<PathBuf>> {
+    let log = Logger::default();
+    let walk = ignore::WalkBuilder::new(dir)
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build();
+
+    let mut config_files = Vec::new();
+    for entry in walk {
+        let entry =
+            entry.map_err(|error| ParseConfigFileError::new(io::Error::other(error), dir))?;
+        let is_file = entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_file());
+        let file_name = entry.file_name().to_string_lossy();
+        if is_file && file_name.ends_with(CONFIG_FILE_SUFFIX) {
+            config_files.push(entry.into_path());
+        }
+    }
+    config_files.sort();
+
+    if config_files.is_empty() {
+        return Err(ParseConfigFileError::new(
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no *{} file found", CONFIG_FILE_SUFFIX),
+            ),
+            dir,
+        ));
+    }
+
+    log.debug(format_args!(
+        "discovered {} config files in {}: {:#?}",
+        config_files.len(),
+        dir.to_string_lossy(),
+        config_files
+    ));
+    Ok(config_files)
+}
@@ -0,0 +1,207 @@
+use std::{fmt::Display, io, path::PathBuf, process::ExitCode};
+
+use crate::{
+    add::AddError,
+    config::ParseConfigFileError,
+    link::CreateLinkError,
+    lock::{ParseLockFileError, WriteLockFileError},
+    remove::RemoveError,
+};
+
+/// Any error that makes a symlonk command fail.
+///
+/// Each variant maps to a process exit code, see [`SymlonkError::exit_code`].
+#[derive(Debug)]
+pub enum SymlonkError {
+    Config(ParseConfigFileError),
+    ParseLockFile {
+        lock_file_path: PathBuf,
+        error: ParseLockFileError,
+    },
+    WriteLockFile {
+        lock_file_path: PathBuf,
+        error: WriteLockFileError,
+    },
+    Verify {
+        config_error_count: usize,
+        created_error_count: usize,
+        target_error_count: usize,
+    },
+    /// `create links --check` found that the lock file would change
+    LockFileOutdated {
+        lock_file_path: PathBuf,
+    },
+    Link(CreateLinkError),
+    /// An error stopped the command, every change it made was reverted
+    RolledBack {
+        reverted: usize,
+        error: CreateLinkError,
+    },
+    Add(AddError),
+    Remove(RemoveError),
+    /// Some selected symlinks could not be deleted
+    Unlink {
+        failed: usize,
+        /// Paths that match no symlink of the lock file
+        unmatched: usize,
+    },
+    /// Another symlonk holds the lock file
+    LockFileBusy {
+        lock_file_path: PathBuf,
+        pid: Option<u32>,
+    },
+    /// The journal of an interrupted apply exists, `recover` must be run
+    InterruptedApply {
+        journal_path: PathBuf,
+    },
+    Io(io::Error),
+}
+
+impl SymlonkError {
+    /// | Code | Meaning                                      |
+    /// |------|----------------------------------------------|
+    /// | 0    | success                                      |
+    /// | 1    | unexpected IO error                          |
+    /// | 2    | invalid command line arguments               |
+    /// | 3    | invalid or unreadable config file            |
+    /// | 4    | invalid, missing or unwritable lock file     |
+    /// | 5    | verification or lock file check failed       |
+    /// | 6    | symlink could not be created                 |
+    /// | 7    | apply failed, its changes were undone        |
+    /// | 8    | file could not be added or removed           |
+    /// | 9    | symlinks not unlinked or paths unmatched     |
+    /// | 10   | interrupted apply, run `recover`             |
+    /// | 11   | lock file used by another symlonk            |
+    pub fn exit_code(&self) -> ExitCode {
+        let code = match self {
+            SymlonkError::Io(_) => 1,
+            SymlonkError::Config(_) => 3,
+            SymlonkError::ParseLockFile { .. } | SymlonkError::WriteLockFile { .. } => 4,
+            SymlonkError::Verify { .. } | SymlonkError::LockFileOutdated { .. } => 5,
+            SymlonkError::Link(_) => 6,
+            SymlonkError::RolledBack { .. } => 7,
+            SymlonkError::Add(_) | SymlonkError::Remove(_) => 8,
+            SymlonkError::Unlink { .. } => 9,
+            SymlonkError::InterruptedApply { .. } => 10,
+            SymlonkError::LockFileBusy { .. } => 11,
+        };
+        ExitCode::from(code)
+    }
+}
+
+impl Display for SymlonkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SymlonkError::Config(error) => error.fmt(f),
+            SymlonkError::ParseLockFile {
+                lock_file_path,
+                error: ParseLockFileError::Io(error),
+            } if error.kind() == io::ErrorKind::NotFound => f.write_fmt(format_args!(
+                "lock file not found: {}",
+                lock_file_path.to_string_lossy()
+            )),
+            SymlonkError::ParseLockFile {
+                lock_file_path,
+                error,
+            } => f.write_fmt(format_args!(
+                "could not parse lock file {}: {}",
+                lock_file_path.to_string_lossy(),
+                error
+            )),
+            SymlonkError::WriteLockFile {
+                lock_file_path,
+                error,
+            } => f.write_fmt(format_args!(
+                "could not write lock file {}: {}",
+                lock_file_path.to_string_lossy(),
+                error
+            )),
+            SymlonkError::Verify {
+                config_error_count,
+                created_error_count,
+                target_error_count,
+            } => f.write_fmt(format_args!(
+                "verify failed: {} problems found ({} config, {} not created, {} invalid targets)",
+                config_error_count + created_error_count + target_error_count,
+                config_error_count,
+                created_error_count,
+                target_error_count
+            )),
+            SymlonkError::LockFileOutdated { lock_file_path } => f.write_fmt(format_args!(
+                "lock file is out of date: {}, run `symlonk create links` to update it",
+                lock_file_path.to_string_lossy()
+            )),
+            SymlonkError::Link(error) => error.fmt(f),
+            SymlonkError::RolledBack { reverted, error } => f.write_fmt(format_args!(
+                "{} (rolled back {} changes)",
+                error, reverted
+            )),
+            SymlonkError::Add(error) => error.fmt(f),
+            SymlonkError::Remove(error) => error.fmt(f),
+            SymlonkError::Unlink { failed, unmatched } => {
+                let mut problems = Vec::new();
+                if *failed > 0 {
+                    problems.push(format!(
+                        "unlink failed for {} symlinks, they are kept in the lock file",
+                        failed
+                    ));
+                }
+                if *unmatched > 0 {
+                    problems.push(format!(
+                        "{} paths match no symlink of the lock file",
+                        unmatched
+                    ));
+                }
+                f.write_str(problems.join(", ").as_str())
+            }
+            SymlonkError::LockFileBusy {
+                lock_file_path,
+                pid,
+            } => f.write_fmt(format_args!(
+                "lock file {} is used by {}, try again when it is done or pass a longer --lock-timeout",
+                lock_file_path.to_string_lossy(),
+                pid.map_or("another symlonk".to_string(), |pid| format!(
+                    "symlonk (PID {})",
+                    pid
+                ))
+            )),
+            SymlonkError::InterruptedApply { journal_path } => f.write_fmt(format_args!(
+                "a previous apply was interrupted, run `symlonk recover finish` or `symlonk recover revert` (journal: {})",
+                journal_path.to_string_lossy()
+            )),
+            SymlonkError::Io(error) => f.write_fmt(format_args!("IO error: {}", error)),
+        }
+    }
+}
+
+impl From<ParseConfigFileError> for SymlonkError {
+    fn from(value: ParseConfigFileError) -> Self {
+        Self::Config(value)
+    }
+}
+
+impl From<CreateLinkError> for SymlonkError {
+    fn from(value: CreateLinkError) -> Self {
+        Self::Link(value)
+    }
+}
+
+impl From<AddError> for SymlonkError {
+    fn from(value: AddError) -> Self {
+        Self::Add(value)
+    }
+}
+
+impl From<RemoveError> for SymlonkError {
+    fn from(value: RemoveError) -> Self {
+        Self::Remove(value)
+    }
+}
+
+impl From<io::Error> for SymlonkError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+pub type SymlonkResult<Res> = Result<Res, SymlonkError>;
@@ -0,0 +1,91 @@
+use std::{
+    ffi::OsString,
+    fs::{File, TryLockError},
+    io::{Read, Seek, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::{SymlonkError, SymlonkResult},
+    log::Logger,
+};
+
+/// Exclusive advisory lock (`flock`) on `<lock file>.lck`, taken by commands
+/// that modify the lock file so that concurrent runs do not lose entries.
+/// The file holds the PID of its owner and is released when dropped.
+#[derive(Debug)]
+pub struct LockFileGuard {
+    file: File,
+}
+
+impl LockFileGuard {
+    pub fn file_path(lock_file_path: &Path) -> PathBuf {
+        let mut file_path = OsString::from(lock_file_path);
+        file_path.push(".lck");
+        PathBuf::from(file_path)
+    }
+
+    /// Wait up to `timeout` for another symlonk holding the lock to finish
+    pub fn acquire(lock_file_path: &Path, timeout: Duration) -> SymlonkResult<Self> {
+        let log = Logger::default();
+        let file_path = Self::file_path(lock_file_path);
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(file_path.as_path())?;
+
+        let start = Instant::now();
+        let mut waiting = false;
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) => {
+                    let pid = read_pid(&mut file);
+                    if start.elapsed() >= timeout {
+                        return Err(SymlonkError::LockFileBusy {
+                            lock_file_path: lock_file_path.to_path_buf(),
+                            pid,
+                        });
+                    }
+                    if !waiting {
+                        log.info(format_args!(
+                            "waiting for {} to release {}",
+                            pid.map_or("another symlonk".to_string(), |pid| format!(
+                                "symlonk (PID {})",
+                                pid
+                            )),
+                            lock_file_path.to_string_lossy()
+                        ));
+                        waiting = true;
+                    }
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+                Err(TryLockError::Error(error)) => return Err(error.into()),
+            }
+        }
+
+        file.set_len(0)?;
+        file.rewind()?;
+        writeln!(file, "{}", std::process::id())?;
+        Ok(Self { file })
+    }
+}
+
+impl Drop for LockFileGuard {
+    fn drop(&mut self) {
+        // the file is kept, deleting it would let a waiting process lock a
+        // file that the next one no longer sees
+        let _ = self.file.set_len(0);
+        let _ = self.file.unlock();
+    }
+}
+
+fn read_pid(file: &mut File) -> Option<u32> {
+    let mut contents = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut contents).ok()?;
+    contents.trim().parse().ok()
+}
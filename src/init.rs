@@ -0,0 +1,165 @@
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{
+    error::{SymlonkError, SymlonkResult},
+    lock::LockFile,
+    log::Logger,
+};
+
+pub const ROOT_CONFIG_FILE_NAME: &str = "root-symlonk.toml";
+pub const SCHEMA_FILE_NAME: &str = "symlonk-schema.json";
+
+pub struct InitOptions {
+    pub source_dir: PathBuf,
+    pub destination_dir: PathBuf,
+
+    /// Add the lock file to the `.gitignore` of the initialized directory
+    pub gitignore: bool,
+}
+
+/// Files written by `init`
+#[derive(Debug, Default, Serialize)]
+pub struct InitReport {
+    pub created: Vec<PathBuf>,
+
+    /// Files that already existed and were left untouched
+    pub skipped: Vec<PathBuf>,
+
+    /// The schema is generated, so it is rewritten on every run
+    pub schema: PathBuf,
+}
+
+/// Scaffold a root config, its JSON schema and an empty lock file in `dir`.
+/// Existing config and lock files are kept.
+pub fn init(dir: &Path, options: &InitOptions) -> SymlonkResult<InitReport> {
+    let log = Logger::default();
+    let mut report = InitReport::default();
+    std::fs::create_dir_all(dir)?;
+
+    // pretty printed with sorted keys, to keep diffs small when regenerated
+    let mut schema = Vec::new();
+    crate::schema::to_writer(&mut schema).map_err(io::Error::from)?;
+    let schema: serde_json::Value = serde_json::from_slice(&schema).map_err(io::Error::from)?;
+    let schema_path = dir.join(SCHEMA_FILE_NAME);
+    let mut schema_file = std::fs::File::create(schema_path.as_path())?;
+    serde_json::to_writer_pretty(&mut schema_file, &schema).map_err(io::Error::from)?;
+    writeln!(schema_file)?;
+    log.success(format_args!(
+        "write schema {}",
+        schema_path.to_string_lossy()
+    ));
+    report.schema = schema_path;
+
+    let config_path = dir.join(ROOT_CONFIG_FILE_NAME);
+    if create_file(config_path.as_path(), root_config(options).as_bytes())? {
+        log.success(format_args!(
+            "create config file {}",
+            config_path.to_string_lossy()
+        ));
+        report.created.push(config_path);
+    } else {
+        log.info(format_args!(
+            "config file already exists: {}",
+            config_path.to_string_lossy()
+        ));
+        report.skipped.push(config_path);
+    }
+
+    let lock_file_path = dir.join(LockFile::DEFAULT_LOCK_FILE_PATH);
+    if crate::path::path_exists(lock_file_path.as_path())? {
+        log.info(format_args!(
+            "lock file already exists: {}",
+            lock_file_path.to_string_lossy()
+        ));
+        report.skipped.push(lock_file_path);
+    } else {
+        let mut lock_file = LockFile::new(lock_file_path.clone());
+        lock_file
+            .write()
+            .map_err(|error| SymlonkError::WriteLockFile {
+                lock_file_path: lock_file_path.clone(),
+                error,
+            })?;
+        log.success(format_args!(
+            "create lock file {}",
+            lock_file_path.to_string_lossy()
+        ));
+        report.created.push(lock_file_path);
+    }
+
+    if options.gitignore {
+        let gitignore_path = dir.join(".gitignore");
+        if add_gitignore_entry(gitignore_path.as_path(), LockFile::DEFAULT_LOCK_FILE_PATH)? {
+            log.success(format_args!(
+                "add {} to {}",
+                LockFile::DEFAULT_LOCK_FILE_PATH,
+                gitignore_path.to_string_lossy()
+            ));
+        }
+    }
+
+    Ok(report)
+}
+
+fn root_config(options: &InitOptions) -> String {
+    let toml_string =
+        |path: &Path| toml::Value::String(path.to_string_lossy().to_string()).to_string();
+
+    format!(
+        r#"#:schema ./{}
+
+[config]
+source_dir = {}
+destination_dir = {}
+
+[symlinks]
+# "path/from/destination" = "path/from/source"
+"#,
+        SCHEMA_FILE_NAME,
+        toml_string(options.source_dir.as_path()),
+        toml_string(options.destination_dir.as_path()),
+    )
+}
+
+/// Write a new file, returns false if the file already exists
+fn create_file(path: &Path, contents: &[u8]) -> io::Result<bool> {
+    match std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+    {
+        Ok(mut file) => {
+            file.write_all(contents)?;
+            Ok(true)
+        }
+        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+/// Append `entry` to the ignore file, returns false if it is already listed
+fn add_gitignore_entry(gitignore_path: &Path, entry: &str) -> io::Result<bool> {
+    let contents = match std::fs::read_to_string(gitignore_path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(error) => return Err(error),
+    };
+    if contents.lines().any(|line| line.trim() == entry) {
+        return Ok(false);
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(gitignore_path)?;
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        writeln!(file)?;
+    }
+    writeln!(file, "{}", entry)?;
+    Ok(true)
+}
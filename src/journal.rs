@@ -0,0 +1,343 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{self, BufRead, Write},
+    os::unix::{self, fs::MetadataExt},
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{SymlonkError, SymlonkResult},
+    lock::{LockFile, LockedLink, ParseLockFileError},
+    log::Logger,
+};
+
+/// File system change made by `create links`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum JournalStep {
+    CreateDir {
+        path: PathBuf,
+    },
+    /// `symlink_target` is the target written in the symlink, which may be
+    /// relative
+    Symlink {
+        link_name: PathBuf,
+        symlink_target: PathBuf,
+    },
+    /// A pruned symlink
+    RemoveSymlink {
+        link_name: PathBuf,
+        symlink_target: PathBuf,
+    },
+    /// An existing file moved to its backup path
+    Backup {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// An overwritten file moved aside, deleted once the apply is committed
+    Trash {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// The lock file was written, only the trash is left to delete
+    Committed,
+}
+
+/// Write-ahead journal of `create links`, stored next to the lock file. Every
+/// step is written to disk before it is applied, so that an interrupted apply
+/// can be reverted or finished by `recover`.
+#[derive(Debug)]
+pub struct Journal {
+    file_path: PathBuf,
+    file: File,
+    steps: Vec<JournalStep>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum RecoverAction {
+    /// Keep the changes of the interrupted apply and record its symlinks in
+    /// the lock file
+    Finish,
+    /// Undo the changes of the interrupted apply
+    Revert,
+}
+
+impl Journal {
+    pub fn file_path(lock_file_path: &Path) -> PathBuf {
+        let mut file_path = OsString::from(lock_file_path);
+        file_path.push(".journal");
+        PathBuf::from(file_path)
+    }
+
+    /// Start a journal, fails if the journal of an interrupted apply exists.
+    /// A journal without steps, left by an apply interrupted before its first
+    /// change, is replaced.
+    pub fn begin(lock_file_path: &Path) -> SymlonkResult<Self> {
+        let file_path = Self::file_path(lock_file_path);
+        let open = || {
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(file_path.as_path())
+        };
+        let file = match open() {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+                if !read_steps(file_path.as_path()).is_ok_and(|steps| steps.is_empty()) {
+                    return Err(SymlonkError::InterruptedApply {
+                        journal_path: file_path,
+                    });
+                }
+                std::fs::remove_file(file_path.as_path())?;
+                open()?
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        Ok(Self {
+            file_path,
+            file,
+            steps: Vec::new(),
+        })
+    }
+
+    /// Write `step` to disk, before applying it
+    pub fn record(&mut self, step: JournalStep) -> io::Result<()> {
+        serde_json::to_writer(&mut self.file, &step)?;
+        writeln!(self.file)?;
+        self.file.sync_data()?;
+        self.steps.push(step);
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        self.file_path.as_path()
+    }
+
+    pub fn step_count(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Undo every step in reverse order and delete the journal. The journal is
+    /// kept if a step could not be undone.
+    pub fn rollback(self) -> SymlonkResult<()> {
+        if revert_steps(&self.steps) {
+            std::fs::remove_file(self.file_path.as_path())?;
+            Ok(())
+        } else {
+            Err(SymlonkError::InterruptedApply {
+                journal_path: self.file_path,
+            })
+        }
+    }
+
+    /// Call once the lock file is written: delete the trash and the journal
+    pub fn commit(mut self) -> io::Result<()> {
+        self.record(JournalStep::Committed)?;
+        delete_trash(&self.steps)?;
+        std::fs::remove_file(self.file_path.as_path())
+    }
+}
+
+/// Revert or finish the apply interrupted while using `lock_file_path`
+pub fn recover(lock_file_path: &Path, action: RecoverAction) -> SymlonkResult<()> {
+    let log = Logger::default();
+    let journal_path = Journal::file_path(lock_file_path);
+    let steps = match read_steps(journal_path.as_path()) {
+        Ok(steps) => steps,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            log.info(format_args!(
+                "nothing to recover, no journal found: {}",
+                journal_path.to_string_lossy()
+            ));
+            return Ok(());
+        }
+        Err(error) => return Err(error.into()),
+    };
+    log.info(format_args!(
+        "journal {} has {} steps",
+        journal_path.to_string_lossy(),
+        steps.len()
+    ));
+
+    let committed = steps
+        .iter()
+        .any(|step| matches!(step, JournalStep::Committed));
+    if committed {
+        log.info(format_args!(
+            "the interrupted apply was committed, finishing it"
+        ));
+    } else {
+        match action {
+            RecoverAction::Revert => {
+                if !revert_steps(&steps) {
+                    return Err(SymlonkError::InterruptedApply { journal_path });
+                }
+            }
+            RecoverAction::Finish => finish_steps(&steps, lock_file_path)?,
+        }
+    }
+
+    if committed || action == RecoverAction::Finish {
+        delete_trash(&steps)?;
+    }
+    std::fs::remove_file(journal_path.as_path())?;
+    log.success(format_args!(
+        "recovered, deleted journal {}",
+        journal_path.to_string_lossy()
+    ));
+    Ok(())
+}
+
+fn read_steps(journal_path: &Path) -> io::Result<Vec<JournalStep>> {
+    let reader = io::BufReader::new(File::open(journal_path)?);
+    let mut steps = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        match serde_json::from_str(line.as_str()) {
+            Ok(step) => steps.push(step),
+            // the last line may be cut by a crash while it was written
+            Err(error) if error.is_eof() => break,
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Ok(steps)
+}
+
+/// Undo steps in reverse order, skipping the ones that were not applied.
+/// Returns false if a step could not be undone.
+fn revert_steps(steps: &[JournalStep]) -> bool {
+    let log = Logger::default();
+    let mut reverted = true;
+
+    for step in steps.iter().rev() {
+        let result = match step {
+            JournalStep::CreateDir { path } => match std::fs::remove_dir(path) {
+                Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+                result => result.map(|()| Some(format!("remove directory {}", path.display()))),
+            },
+            JournalStep::Symlink {
+                link_name,
+                symlink_target,
+            } => match link_name.read_link() {
+                Ok(target) if &target == symlink_target => std::fs::remove_file(link_name)
+                    .map(|()| Some(format!("unlink {}", link_name.display()))),
+                _ => Ok(None),
+            },
+            JournalStep::RemoveSymlink {
+                link_name,
+                symlink_target,
+            } => match crate::path::path_exists(link_name) {
+                Ok(false) => unix::fs::symlink(symlink_target, link_name).map(|()| {
+                    Some(format!(
+                        "link {} to {}",
+                        link_name.display(),
+                        symlink_target.display()
+                    ))
+                }),
+                result => result.map(|_| None),
+            },
+            JournalStep::Backup { from, to } | JournalStep::Trash { from, to } => {
+                match (crate::path::path_exists(from), crate::path::path_exists(to)) {
+                    (Ok(false), Ok(true)) => std::fs::rename(to, from).map(|()| {
+                        Some(format!("move {} back to {}", to.display(), from.display()))
+                    }),
+                    (Ok(true), Ok(true)) if is_hard_link_of(to, from).unwrap_or(false) => {
+                        std::fs::remove_file(to).map(|()| Some(format!("remove {}", to.display())))
+                    }
+                    (Err(error), _) | (_, Err(error)) => Err(error),
+                    _ => Ok(None),
+                }
+            }
+            JournalStep::Committed => Ok(None),
+        };
+
+        match result {
+            Ok(Some(description)) => log.info(format_args!("rollback: {}", description)),
+            Ok(None) => {}
+            Err(error) => {
+                log.error(format_args!("rollback: {:?}: {}", step, error));
+                reverted = false;
+            }
+        }
+    }
+
+    reverted
+}
+
+/// Record the symlinks created or pruned by the steps in the lock file
+fn finish_steps(steps: &[JournalStep], lock_file_path: &Path) -> SymlonkResult<()> {
+    let mut lock_file = match crate::lock::parse_lock_file(lock_file_path) {
+        Ok(lock_file) => lock_file,
+        Err(ParseLockFileError::Io(error)) if error.kind() == io::ErrorKind::NotFound => {
+            LockFile::new(lock_file_path.to_path_buf())
+        }
+        Err(error) => {
+            return Err(SymlonkError::ParseLockFile {
+                lock_file_path: lock_file_path.to_path_buf(),
+                error,
+            })
+        }
+    };
+
+    for step in steps {
+        match step {
+            JournalStep::Symlink {
+                link_name,
+                symlink_target,
+            } if link_name
+                .read_link()
+                .is_ok_and(|target| &target == symlink_target) =>
+            {
+                let link_target = crate::path::read_link_absolute(link_name)?;
+                lock_file.set_symlink(
+                    link_name,
+                    LockedLink {
+                        relative: symlink_target.is_relative(),
+                        ..LockedLink::new(link_target)
+                    },
+                );
+            }
+            JournalStep::RemoveSymlink { link_name, .. }
+                if !crate::path::path_exists(link_name)? =>
+            {
+                lock_file.remove_symlink(link_name);
+            }
+            _ => {}
+        }
+    }
+
+    lock_file
+        .write()
+        .map_err(|error| SymlonkError::WriteLockFile {
+            lock_file_path: lock_file_path.to_path_buf(),
+            error,
+        })
+}
+
+/// An overwritten file is hard linked to its trash path until the symlink
+/// replaces it
+fn is_hard_link_of(path: &Path, other: &Path) -> io::Result<bool> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    let other_metadata = std::fs::symlink_metadata(other)?;
+    Ok(metadata.dev() == other_metadata.dev() && metadata.ino() == other_metadata.ino())
+}
+
+fn delete_trash(steps: &[JournalStep]) -> io::Result<()> {
+    for step in steps {
+        if let JournalStep::Trash { to, .. } = step {
+            match std::fs::symlink_metadata(to) {
+                // an overwritten directory is always empty
+                Ok(metadata) if metadata.is_dir() => std::fs::remove_dir(to)?,
+                Ok(_) => std::fs::remove_file(to)?,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                Err(error) => return Err(error),
+            }
+        }
+    }
+    Ok(())
+}
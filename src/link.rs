@@ -1,21 +1,146 @@
-use std::{ffi::OsString, io, os::unix, path::Path, time::SystemTime};
+use std::{
+    ffi::OsString,
+    fmt::Display,
+    io,
+    os::unix::{self, fs::PermissionsExt},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
-use crate::log::{self, Logger};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
 
-pub struct CreateLinkOptions {
-    overwrite_all: bool,
-    backup_all: bool,
-    skip_all: bool,
+use crate::{
+    journal::{Journal, JournalStep},
+    log::{self, Logger},
+};
+
+/// What to do when a file already exists where a symlink should be created
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Leave the existing file untouched
+    Skip,
+    /// Delete the existing file
+    Overwrite,
+    /// Rename the existing file with a `.backup` suffix
+    Backup,
+    /// Abort with an error
+    Fail,
+}
+
+impl Display for ConflictPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ConflictPolicy::Skip => "skip",
+            ConflictPolicy::Overwrite => "overwrite",
+            ConflictPolicy::Backup => "backup",
+            ConflictPolicy::Fail => "fail",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug)]
+pub enum CreateLinkError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// The conflict policy is [`ConflictPolicy::Fail`]
+    AlreadyExists {
+        link_name: PathBuf,
+        link_target: PathBuf,
+    },
+    /// No conflict policy was given and stdin is not a terminal
+    NotInteractive {
+        link_name: PathBuf,
+        link_target: PathBuf,
+    },
+}
+
+impl CreateLinkError {
+    fn io(path: &Path, error: io::Error) -> Self {
+        Self::Io {
+            path: path.to_path_buf(),
+            error,
+        }
+    }
+}
+
+impl Display for CreateLinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CreateLinkError::Io { path, error } => {
+                f.write_fmt(format_args!("{}: {}", path.to_string_lossy(), error))
+            }
+            CreateLinkError::AlreadyExists {
+                link_name,
+                link_target,
+            } => f.write_fmt(format_args!(
+                "file already exists: {} (trying to link to {})",
+                link_name.to_string_lossy(),
+                link_target.to_string_lossy()
+            )),
+            CreateLinkError::NotInteractive {
+                link_name,
+                link_target,
+            } => f.write_fmt(format_args!(
+                "file already exists: {} (trying to link to {}), stdin is not a terminal, use --on-conflict to choose an action",
+                link_name.to_string_lossy(),
+                link_target.to_string_lossy()
+            )),
+        }
+    }
+}
+
+pub struct CreateLinkOptions<'a> {
+    /// Prompt for each conflict if `None`
+    on_conflict: Option<ConflictPolicy>,
+
+    /// Record every change, overwritten files are moved aside instead of
+    /// deleted so that they can be restored
+    journal: Option<&'a mut Journal>,
 }
 
-impl CreateLinkOptions {
-    pub fn new(overwrite_all: bool, backup_all: bool, skip_all: bool) -> Self {
+impl<'a> CreateLinkOptions<'a> {
+    pub fn new(on_conflict: Option<ConflictPolicy>) -> Self {
         Self {
-            overwrite_all,
-            backup_all,
-            skip_all,
+            on_conflict,
+            journal: None,
         }
     }
+
+    pub fn with_journal(mut self, journal: &'a mut Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    pub fn journal_mut(&mut self) -> Option<&mut Journal> {
+        self.journal.as_deref_mut()
+    }
+
+    fn record(&mut self, step: JournalStep) -> Result<(), CreateLinkError> {
+        let Some(journal) = self.journal.as_deref_mut() else {
+            return Ok(());
+        };
+        journal
+            .record(step)
+            .map_err(|error| CreateLinkError::io(journal.path(), error))
+    }
+}
+
+/// Per-symlink options from the config file
+#[derive(Clone, Debug, Default)]
+pub struct LinkOptions {
+    /// Point the symlink to a path relative to its parent directory
+    pub relative: bool,
+
+    /// Permissions to set on the symlink target
+    pub mode: Option<u32>,
+
+    /// Overrides the conflict policy of the command
+    pub on_conflict: Option<ConflictPolicy>,
 }
 
 struct ParseInputError;
@@ -45,26 +170,50 @@ impl TryFrom<char> for CreateLinkPromptAction {
     }
 }
 
-/// Return true if link is created (including if it already existed)
+#[derive(Debug)]
+pub enum CreateLinkOutcome {
+    /// The symlink already pointed to the target
+    AlreadyLinked,
+    Created,
+    /// The existing file was deleted before creating the symlink
+    Overwritten,
+    /// The existing file was moved to `backup_path` before creating the symlink
+    BackedUp {
+        backup_path: PathBuf,
+    },
+    /// The existing file was left untouched
+    Skipped,
+    /// The symlink's parent exists but is not a directory
+    ParentNotADirectory(PathBuf),
+}
+
+impl CreateLinkOutcome {
+    /// Return true if link is created (including if it already existed)
+    pub fn is_linked(&self) -> bool {
+        match self {
+            CreateLinkOutcome::AlreadyLinked
+            | CreateLinkOutcome::Created
+            | CreateLinkOutcome::Overwritten
+            | CreateLinkOutcome::BackedUp { .. } => true,
+            CreateLinkOutcome::Skipped | CreateLinkOutcome::ParentNotADirectory(_) => false,
+        }
+    }
+}
+
 pub fn create_link(
     link_name: &Path,
     link_target: &Path,
+    link_options: &LinkOptions,
     options: &mut CreateLinkOptions,
-) -> std::io::Result<bool> {
-    let does_destination_exist = crate::path::path_exists(link_name)?;
-    let is_all_action = options.overwrite_all || options.backup_all || options.skip_all;
-    let mut action = None;
-    let mut overwrite = false; // options.overwrite_all;
-    let mut backup = false; //options.backup_all;
-    let mut skip = false; //options.skip_all;
+) -> Result<CreateLinkOutcome, CreateLinkError> {
     let log = log::Logger::default();
+    let mut conflict_action = None;
+    let mut outcome = CreateLinkOutcome::Created;
+    let mut replace = false;
 
-    if does_destination_exist && !is_all_action {
-        let current_target = if link_name.is_symlink() {
-            link_name.read_link().expect("read_link")
-        } else {
-            link_name.to_path_buf()
-        };
+    if crate::path::path_exists(link_name).map_err(|error| CreateLinkError::io(link_name, error))? {
+        let current_target =
+            current_target(link_name).map_err(|error| CreateLinkError::io(link_name, error))?;
 
         if current_target.as_path() == link_target {
             log.info(format_args!(
@@ -72,106 +221,329 @@ pub fn create_link(
                 link_name.to_string_lossy(),
                 link_target.to_string_lossy()
             ));
-            // skip = true;
-            return Ok(true);
-        } else {
-            action = prompt_existing_destination(link_name, link_target)?;
+            apply_mode(link_target, link_options.mode)?;
+            return Ok(CreateLinkOutcome::AlreadyLinked);
         }
-    }
 
-    match action {
-        Some(CreateLinkPromptAction::Overwrite) => overwrite = true,
-        Some(CreateLinkPromptAction::Backup) => backup = true,
-        Some(CreateLinkPromptAction::Skip) => skip = true,
-        Some(CreateLinkPromptAction::OverwriteAll) => options.overwrite_all = true,
-        Some(CreateLinkPromptAction::BackupAll) => options.backup_all = true,
-        Some(CreateLinkPromptAction::SkipAll) => options.skip_all = true,
-        None => (),
+        conflict_action = Some(match link_options.on_conflict.or(options.on_conflict) {
+            Some(policy) => policy,
+            None => prompt_existing_destination(link_name, link_target, options)?,
+        });
     }
 
-    if skip || options.skip_all {
-        // log.info(format!("skipped {}", link_name.to_string_lossy()).as_str())
-    } else {
-        if backup || options.backup_all {
-            let mut backup_name = OsString::from(link_name);
-            backup_name.push(".backup");
-
-            if crate::path::path_exists(backup_name.as_os_str())? {
-                let timestamp = SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_millis();
-
-                log.info(format_args!(
-                    "{} already exists",
-                    backup_name.to_string_lossy()
-                ));
-                backup_name = OsString::from(format!(
-                    "{}.{}.backup",
-                    link_name.to_string_lossy(),
-                    timestamp
-                ));
-            }
-
-            std::fs::rename(link_name, backup_name.as_os_str())?;
+    match conflict_action {
+        Some(ConflictPolicy::Skip) => {
+            log.info(format_args!(
+                "skip {}, file already exists",
+                link_name.to_string_lossy()
+            ));
+            return Ok(CreateLinkOutcome::Skipped);
+        }
+        Some(ConflictPolicy::Fail) => {
+            return Err(CreateLinkError::AlreadyExists {
+                link_name: link_name.to_path_buf(),
+                link_target: link_target.to_path_buf(),
+            });
+        }
+        Some(ConflictPolicy::Backup) => {
+            let backup_path = unused_path(link_name, "backup")?;
+            let is_dir = std::fs::symlink_metadata(link_name)
+                .map_err(|error| CreateLinkError::io(link_name, error))?
+                .is_dir();
+            options.record(JournalStep::Backup {
+                from: link_name.to_path_buf(),
+                to: backup_path.clone(),
+            })?;
+            let moved = move_aside(link_name, backup_path.as_path(), is_dir)?;
 
             log.success(format_args!(
-                "moved {} to {}",
+                "{} {} to {}",
+                if moved { "moved" } else { "backed up" },
                 link_name.to_string_lossy(),
-                backup_name.to_string_lossy(),
-            ))
+                backup_path.to_string_lossy(),
+            ));
+            replace = !is_dir;
+            outcome = CreateLinkOutcome::BackedUp { backup_path };
         }
-
-        if overwrite || options.overwrite_all {
-            let is_dir = std::fs::metadata(link_name).expect("fs::metadata").is_dir();
+        Some(ConflictPolicy::Overwrite) => {
+            let is_dir = std::fs::symlink_metadata(link_name)
+                .map_err(|error| CreateLinkError::io(link_name, error))?
+                .is_dir();
+            // like `remove_dir`, only an empty directory is overwritten
+            if is_dir
+                && std::fs::read_dir(link_name)
+                    .map_err(|error| CreateLinkError::io(link_name, error))?
+                    .next()
+                    .is_some()
+            {
+                return Err(CreateLinkError::io(
+                    link_name,
+                    io::Error::from(io::ErrorKind::DirectoryNotEmpty),
+                ));
+            }
+            if options.journal.is_some() {
+                let trash_path = unused_path(link_name, "symlonk-trash")?;
+                options.record(JournalStep::Trash {
+                    from: link_name.to_path_buf(),
+                    to: trash_path.clone(),
+                })?;
+                move_aside(link_name, trash_path.as_path(), is_dir)?;
+            } else if is_dir {
+                std::fs::remove_dir(link_name)
+                    .map_err(|error| CreateLinkError::io(link_name, error))?;
+            }
             if is_dir {
-                std::fs::remove_dir(link_name).expect("remove_dir")
-            } else {
-                std::fs::remove_file(link_name).expect("remove_file");
+                log.success(format_args!("removed {}", link_name.to_string_lossy()));
             }
-            log.success(format_args!("removed {}", link_name.to_string_lossy()))
+            replace = !is_dir;
+            outcome = CreateLinkOutcome::Overwritten;
         }
+        None => (),
+    }
 
-        let link_parent = link_name.parent();
-        if let Some(parent_path) = link_parent {
-            if !parent_path.exists() {
-                std::fs::create_dir_all(parent_path)?;
-                log.success(format_args!(
-                    "create directory {}",
-                    parent_path.to_string_lossy()
-                ));
-            } else if !parent_path.is_dir() {
-                log.error(format_args!(
-                    "symlink parent is not a directory: {}",
-                    parent_path.to_string_lossy(),
-                ));
-                return Ok(false);
-            }
+    let link_parent = link_name.parent();
+    if let Some(parent_path) = link_parent {
+        if !parent_path.exists() {
+            create_dir_all(parent_path, options)?;
+            log.success(format_args!(
+                "create directory {}",
+                parent_path.to_string_lossy()
+            ));
+        } else if !parent_path.is_dir() {
+            log.error(format_args!(
+                "symlink parent is not a directory: {}",
+                parent_path.to_string_lossy(),
+            ));
+            return Ok(CreateLinkOutcome::ParentNotADirectory(
+                parent_path.to_path_buf(),
+            ));
         }
+    }
+
+    let symlink_target = match (link_options.relative, link_name.parent()) {
+        (true, Some(parent_path)) => crate::path::relative_to(link_target, parent_path),
+        _ => link_target.to_path_buf(),
+    };
+    options.record(JournalStep::Symlink {
+        link_name: link_name.to_path_buf(),
+        symlink_target: symlink_target.clone(),
+    })?;
+    if replace {
+        replace_with_symlink(symlink_target.as_path(), link_name)
+    } else {
+        unix::fs::symlink(symlink_target.as_path(), link_name)
+    }
+    .map_err(|error| CreateLinkError::io(link_name, error))?;
+    log.success(format_args!(
+        "{} {} to {}",
+        if replace { "relink" } else { "link" },
+        link_name.to_string_lossy(),
+        symlink_target.to_string_lossy()
+    ));
+    apply_mode(link_target, link_options.mode)?;
+
+    Ok(outcome)
+}
 
-        unix::fs::symlink(link_target, link_name).expect("symlink");
-        log.success(format_args!(
-            "link {} to {}",
+/// `<link_name>.<extension>`, or `<link_name>.<timestamp>.<extension>` if that
+/// path is taken
+fn unused_path(link_name: &Path, extension: &str) -> Result<PathBuf, CreateLinkError> {
+    let log = log::Logger::default();
+    let mut path = OsString::from(link_name);
+    path.push(".");
+    path.push(extension);
+
+    if crate::path::path_exists(path.as_os_str())
+        .map_err(|error| CreateLinkError::io(Path::new(&path), error))?
+    {
+        let timestamp = SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+
+        log.info(format_args!("{} already exists", path.to_string_lossy()));
+        path = OsString::from(format!(
+            "{}.{}.{}",
             link_name.to_string_lossy(),
+            timestamp,
+            extension
+        ));
+    }
+    Ok(PathBuf::from(path))
+}
+
+/// Hard link a file to `aside_path`, so that it stays in place until the
+/// symlink is renamed over it. A directory, or a file that cannot be hard
+/// linked (no hard link support, `fs.protected_hardlinks`), is renamed instead
+/// and is missing until the symlink is created. Returns whether it was renamed.
+fn move_aside(path: &Path, aside_path: &Path, is_dir: bool) -> Result<bool, CreateLinkError> {
+    let log = log::Logger::default();
+    if !is_dir {
+        match std::fs::hard_link(path, aside_path) {
+            Ok(()) => return Ok(false),
+            Err(error) => log.debug(format_args!(
+                "cannot hard link {} to {}, renaming it: {}",
+                path.to_string_lossy(),
+                aside_path.to_string_lossy(),
+                error
+            )),
+        }
+    }
+    std::fs::rename(path, aside_path).map_err(|error| CreateLinkError::io(path, error))?;
+    Ok(true)
+}
+
+/// Create the symlink under a temporary name in the same directory and rename
+/// it over `link_name`, so that `link_name` never stops existing
+fn replace_with_symlink(symlink_target: &Path, link_name: &Path) -> io::Result<()> {
+    let mut temp_name = OsString::from(".");
+    temp_name.push(link_name.file_name().unwrap_or_default());
+    temp_name.push(".symlonk-tmp");
+    let temp_path = link_name.with_file_name(temp_name);
+
+    // left over by an interrupted run
+    match std::fs::remove_file(temp_path.as_path()) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+        _ => {}
+    }
+    unix::fs::symlink(symlink_target, temp_path.as_path())?;
+    std::fs::rename(temp_path.as_path(), link_name).inspect_err(|_| {
+        let _ = std::fs::remove_file(temp_path.as_path());
+    })
+}
+
+/// `path` and its ancestors that do not exist, outermost first
+fn missing_ancestors(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut missing_dirs = Vec::new();
+    for ancestor in path.ancestors() {
+        if crate::path::path_exists(ancestor)? {
+            break;
+        }
+        missing_dirs.push(ancestor.to_path_buf());
+    }
+    missing_dirs.reverse();
+    Ok(missing_dirs)
+}
+
+/// Like [`std::fs::create_dir_all`], but records each created directory
+fn create_dir_all(path: &Path, options: &mut CreateLinkOptions) -> Result<(), CreateLinkError> {
+    let missing_dirs = missing_ancestors(path).map_err(|error| CreateLinkError::io(path, error))?;
+
+    for dir in missing_dirs {
+        options.record(JournalStep::CreateDir { path: dir.clone() })?;
+        std::fs::create_dir(dir.as_path())
+            .map_err(|error| CreateLinkError::io(dir.as_path(), error))?;
+    }
+
+    Ok(())
+}
+
+fn apply_mode(link_target: &Path, mode: Option<u32>) -> Result<(), CreateLinkError> {
+    let Some(mode) = mode else {
+        return Ok(());
+    };
+
+    let permissions = std::fs::metadata(link_target)
+        .map_err(|error| CreateLinkError::io(link_target, error))?
+        .permissions();
+    if permissions.mode() & 0o7777 != mode {
+        std::fs::set_permissions(link_target, std::fs::Permissions::from_mode(mode))
+            .map_err(|error| CreateLinkError::io(link_target, error))?;
+        Logger::default().success(format_args!(
+            "set mode {:04o} on {}",
+            mode,
             link_target.to_string_lossy()
         ));
+    }
+
+    Ok(())
+}
+
+/// What [`create_link`] would do for a given symlink, without touching the file system
+#[derive(Debug)]
+pub enum LinkPlan {
+    /// The symlink already points to the requested target
+    AlreadyLinked,
+
+    /// Something else exists at the symlink path, creating the link would prompt
+    Conflict { current_target: PathBuf },
+
+    /// The symlink would be created, along with its missing ancestor
+    /// directories
+    Create { missing_dirs: Vec<PathBuf> },
 
-        return Ok(true);
+    /// The symlink's parent exists but is not a directory
+    ParentNotADirectory(PathBuf),
+}
+
+pub fn plan_link(link_name: &Path, link_target: &Path) -> io::Result<LinkPlan> {
+    if crate::path::path_exists(link_name)? {
+        let current_target = current_target(link_name)?;
+
+        if current_target.as_path() == link_target {
+            return Ok(LinkPlan::AlreadyLinked);
+        }
+        return Ok(LinkPlan::Conflict { current_target });
+    }
+
+    let mut missing_dirs = Vec::new();
+    if let Some(parent_path) = link_name.parent() {
+        if !parent_path.exists() {
+            missing_dirs = missing_ancestors(parent_path)?;
+        } else if !parent_path.is_dir() {
+            return Ok(LinkPlan::ParentNotADirectory(parent_path.to_path_buf()));
+        }
     }
 
-    Ok(false)
+    Ok(LinkPlan::Create { missing_dirs })
+}
+
+/// Absolute target of the symlink at `link_name`, or `link_name` itself if it
+/// is not a symlink. Relative targets are resolved from the symlink's parent
+fn current_target(link_name: &Path) -> io::Result<PathBuf> {
+    if link_name.is_symlink() {
+        crate::path::read_link_absolute(link_name)
+    } else {
+        Ok(link_name.to_path_buf())
+    }
 }
 
 fn prompt_existing_destination(
     link_name: &Path,
     link_target: &Path,
-) -> io::Result<Option<CreateLinkPromptAction>> {
+    options: &mut CreateLinkOptions,
+) -> Result<ConflictPolicy, CreateLinkError> {
     let log = Logger::default();
+    if !log::is_interactive() {
+        return Err(CreateLinkError::NotInteractive {
+            link_name: link_name.to_path_buf(),
+            link_target: link_target.to_path_buf(),
+        });
+    }
+
     let input_char = log.prompt_char(format_args!(
         "File already exists: {} (trying to link to {}), what do you want to do?\n[s]kip, [S]kip all, [o]verwrite, [O]verwrite all, [b]ackup, [B]ackup all?",
         link_name.to_string_lossy(),
         link_target.to_string_lossy(),
-    ))?;
+    )).map_err(|error| CreateLinkError::io(Path::new("<stdin>"), error))?;
+
+    let policy = match input_char.and_then(|ch| CreateLinkPromptAction::try_from(ch).ok()) {
+        Some(CreateLinkPromptAction::Overwrite) => ConflictPolicy::Overwrite,
+        Some(CreateLinkPromptAction::Backup) => ConflictPolicy::Backup,
+        Some(CreateLinkPromptAction::Skip) | None => ConflictPolicy::Skip,
+        Some(CreateLinkPromptAction::OverwriteAll) => {
+            options.on_conflict = Some(ConflictPolicy::Overwrite);
+            ConflictPolicy::Overwrite
+        }
+        Some(CreateLinkPromptAction::BackupAll) => {
+            options.on_conflict = Some(ConflictPolicy::Backup);
+            ConflictPolicy::Backup
+        }
+        Some(CreateLinkPromptAction::SkipAll) => {
+            options.on_conflict = Some(ConflictPolicy::Skip);
+            ConflictPolicy::Skip
+        }
+    };
 
-    Ok(input_char.and_then(|ch| CreateLinkPromptAction::try_from(ch).ok()))
+    Ok(policy)
 }
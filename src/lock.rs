@@ -1,19 +1,36 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::OsString,
     fmt::Display,
-    io,
+    fs::File,
+    io::{self, Write},
     path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 
-use crate::log::Logger;
+use crate::{
+    error::{SymlonkError, SymlonkResult},
+    log::Logger,
+    output::OutputFormat,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
 pub enum LockFileVerifyError {
-    IoError(std::io::Error),
-    NotASymlink(PathBuf),
-    SymlinkNotFound(PathBuf),
+    IoError {
+        link_name: PathBuf,
+        #[serde(serialize_with = "crate::output::serialize_display")]
+        error: std::io::Error,
+    },
+    NotASymlink {
+        link_name: PathBuf,
+    },
+    SymlinkNotFound {
+        link_name: PathBuf,
+    },
     SymlinkTargetNotFound {
         link_name: PathBuf,
         link_target: PathBuf,
@@ -25,10 +42,12 @@ pub enum LockFileVerifyError {
     },
     // SymlinkNotFoundInLockFile(PathBuf),
     SymlinkNotFoundInLockFile {
-        symlink_name: PathBuf,
+        link_name: PathBuf,
         lock_file_path: PathBuf,
     },
-    SymlinkNotFoundInConfig(PathBuf),
+    SymlinkNotFoundInConfig {
+        link_name: PathBuf,
+    },
     InvalidSymlinkTargetInLockFile {
         link_name: PathBuf,
         lock_file_link_target: Option<PathBuf>,
@@ -39,12 +58,12 @@ pub enum LockFileVerifyError {
 impl Display for LockFileVerifyError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            LockFileVerifyError::NotASymlink(path) => {
-                f.write_fmt(format_args!("not a symlink: {}", path.to_string_lossy()))
+            LockFileVerifyError::NotASymlink { link_name } => {
+                f.write_fmt(format_args!("not a symlink: {}", link_name.to_string_lossy()))
             }
-            LockFileVerifyError::SymlinkNotFound(path) => f.write_fmt(format_args!(
+            LockFileVerifyError::SymlinkNotFound { link_name } => f.write_fmt(format_args!(
                 "symlink not found: {}",
-                path.to_string_lossy()
+                link_name.to_string_lossy()
             )),
             LockFileVerifyError::SymlinkTargetNotFound {
                 link_name,
@@ -70,16 +89,16 @@ impl Display for LockFileVerifyError {
                     lock_file_link_target.to_string_lossy()
                 ))
             }
-            LockFileVerifyError::SymlinkNotFoundInLockFile { symlink_name, lock_file_path } => {
+            LockFileVerifyError::SymlinkNotFoundInLockFile { link_name, lock_file_path } => {
                 f.write_fmt(format_args!(
                     "symlink from config not found in lock file: {}, was it generated using the following lock file ? {}",
-                    symlink_name.to_string_lossy(),
+                    link_name.to_string_lossy(),
                     lock_file_path.to_string_lossy(),
                 ))
             },
-            LockFileVerifyError::SymlinkNotFoundInConfig(path) => f.write_fmt(format_args!(
+            LockFileVerifyError::SymlinkNotFoundInConfig { link_name } => f.write_fmt(format_args!(
                 "outdated symlink in lock file: {} is not in config, run with --prune to delete outdated symlinks",
-                path.to_string_lossy()
+                link_name.to_string_lossy()
             )),
             LockFileVerifyError::InvalidSymlinkTargetInLockFile {
                 link_name,
@@ -97,16 +116,118 @@ impl Display for LockFileVerifyError {
                     config_link_target.to_string_lossy(),
                 ))
             },
-            LockFileVerifyError::IoError(error)=> f.write_fmt(format_args!("IO error: {:?}", error)),
+            LockFileVerifyError::IoError { link_name, error } => f.write_fmt(format_args!(
+                "IO error: {}: {}",
+                link_name.to_string_lossy(),
+                error
+            )),
             // _ => f.write_fmt(format_args!("{:?}", self)),
         }
     }
 }
 
+/// How a file is linked, symlonk only creates symlinks for now
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    #[default]
+    Symlink,
+    Hardlink,
+    Copy,
+}
+
+/// A symlink recorded in the lock file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LockedLink {
+    /// Absolute target, even if the symlink stores a relative path
+    pub target: PathBuf,
+
+    #[serde(default)]
+    pub kind: LinkKind,
+
+    /// The symlink stores a path relative to its parent directory
+    #[serde(default)]
+    pub relative: bool,
+
+    /// RFC 3339 UTC timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+
+    /// Config file that declares the symlink
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub declared_in: Option<PathBuf>,
+
+    /// Where the file found at the symlink path was moved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_path: Option<PathBuf>,
+}
+
+impl LockedLink {
+    fn map_paths(&self, map_path: impl Fn(&Path) -> PathBuf) -> Self {
+        Self {
+            target: map_path(self.target.as_path()),
+            declared_in: self.declared_in.as_deref().map(&map_path),
+            backup_path: self.backup_path.as_deref().map(&map_path),
+            ..self.clone()
+        }
+    }
+
+    /// A symlink created now
+    pub fn new(target: PathBuf) -> Self {
+        Self {
+            target,
+            kind: LinkKind::Symlink,
+            relative: false,
+            created_at: Some(format_timestamp(SystemTime::now())),
+            declared_in: None,
+            backup_path: None,
+        }
+    }
+}
+
+/// How paths are written in the lock file. They are always absolute once read.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum LockPaths {
+    #[default]
+    Absolute,
+    /// Paths inside the home directory start with `~/`, so that the lock file
+    /// is valid for every user
+    Home,
+    /// Paths are relative to the directory of the lock file
+    LockDir,
+}
+
+/// Lock file of the first format, a table of symlink names to targets
+#[derive(Deserialize)]
+struct LockFileV1 {
+    #[serde(default)]
+    symlinks: HashMap<PathBuf, PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct LockFileHeader {
+    version: Option<i64>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct LockFile {
+    version: i64,
+
+    /// Host that wrote the lock file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hostname: Option<String>,
+
+    /// RFC 3339 UTC timestamp of the last write
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    generated_at: Option<String>,
+
     #[serde(default)]
-    symlinks: HashMap<PathBuf, PathBuf>,
+    paths: LockPaths,
+
+    /// Sorted, so that the lock file does not change between runs
+    #[serde(default)]
+    symlinks: BTreeMap<PathBuf, LockedLink>,
 
     #[serde(skip, default = "LockFile::default_path")]
     file_path: PathBuf,
@@ -114,10 +235,15 @@ pub struct LockFile {
 
 impl LockFile {
     pub const DEFAULT_LOCK_FILE_PATH: &'static str = "symlonk-lock.toml";
+    pub const VERSION: i64 = 2;
 
     pub fn new(file_path: PathBuf) -> Self {
         Self {
-            symlinks: HashMap::new(),
+            version: Self::VERSION,
+            hostname: None,
+            generated_at: None,
+            paths: LockPaths::Absolute,
+            symlinks: BTreeMap::new(),
             file_path,
         }
     }
@@ -126,23 +252,35 @@ impl LockFile {
         PathBuf::from(Self::DEFAULT_LOCK_FILE_PATH)
     }
 
-    pub fn set_symlink(&mut self, name: &Path, target: &Path) -> Option<PathBuf> {
+    /// Record a symlink, returns its previous target. The creation time and
+    /// backup path are kept if the target did not change.
+    pub fn set_symlink(&mut self, name: &Path, mut link: LockedLink) -> Option<PathBuf> {
+        let previous = self.symlinks.get(name);
+        if let Some(previous) = previous.filter(|previous| previous.target == link.target) {
+            link.created_at = previous.created_at.clone().or(link.created_at);
+            link.backup_path = link.backup_path.or(previous.backup_path.clone());
+        }
         self.symlinks
-            .insert(name.to_path_buf(), target.to_path_buf())
+            .insert(name.to_path_buf(), link)
+            .map(|previous| previous.target)
     }
 
     pub fn remove_symlink(&mut self, name: &Path) -> Option<PathBuf> {
-        self.symlinks.remove(name)
+        self.symlinks.remove(name).map(|link| link.target)
     }
 
-    pub fn remove_symlinks(&mut self) {
-        self.symlinks.clear()
+    pub fn set_paths(&mut self, paths: LockPaths) {
+        self.paths = paths;
     }
 
-    pub fn symlinks(&self) -> &HashMap<PathBuf, PathBuf> {
+    pub fn symlinks(&self) -> &BTreeMap<PathBuf, LockedLink> {
         &self.symlinks
     }
 
+    pub fn target(&self, name: &Path) -> Option<&PathBuf> {
+        self.symlinks.get(name).map(|link| &link.target)
+    }
+
     pub fn symlink_count(&self) -> usize {
         self.symlinks.len()
     }
@@ -150,17 +288,20 @@ impl LockFile {
     pub fn verify_config(
         &self,
         config_symlinks: &HashMap<PathBuf, PathBuf>,
-    ) -> Result<(), LockFileVerifyError> {
+    ) -> Vec<LockFileVerifyError> {
+        let mut errors = Vec::new();
         let mut lock_file_keys = HashSet::new();
         for link_name in self.symlinks.keys() {
             lock_file_keys.insert(link_name);
         }
 
+        let mut config_symlinks: Vec<_> = config_symlinks.iter().collect();
+        config_symlinks.sort();
         for (config_link_name, config_link_target) in config_symlinks {
-            match self.symlinks.get(config_link_name.as_path()) {
+            match self.target(config_link_name.as_path()) {
                 Some(lock_symlink_target) => {
                     if lock_symlink_target.as_path() != config_link_target.as_path() {
-                        return Err(LockFileVerifyError::InvalidSymlinkTargetInLockFile {
+                        errors.push(LockFileVerifyError::InvalidSymlinkTargetInLockFile {
                             link_name: config_link_name.clone(),
                             lock_file_link_target: Some(lock_symlink_target.clone()),
                             config_link_target: config_link_target.clone(),
@@ -171,78 +312,108 @@ impl LockFile {
                 }
 
                 None => {
-                    return Err(LockFileVerifyError::SymlinkNotFoundInLockFile {
-                        symlink_name: config_link_name.clone(),
+                    errors.push(LockFileVerifyError::SymlinkNotFoundInLockFile {
+                        link_name: config_link_name.clone(),
                         lock_file_path: self.file_path.clone(),
                     });
                 }
             }
         }
 
-        if let Some(&x) = lock_file_keys.iter().next() {
-            return Err(LockFileVerifyError::SymlinkNotFoundInConfig(x.clone()));
+        let mut lock_file_keys: Vec<_> = lock_file_keys.into_iter().collect();
+        lock_file_keys.sort();
+        for link_name in lock_file_keys {
+            errors.push(LockFileVerifyError::SymlinkNotFoundInConfig {
+                link_name: link_name.clone(),
+            });
         }
 
-        Ok(())
+        errors
     }
 
-    // pub fn verify_symlinks(&self) -> Result<(), LockFileVerifyError> {
-    //     self.verify_symlinks_created()?;
-    //     self.verify_symlink_targets_exist()?;
-    //     Ok(())
-    // }
+    pub fn verify_symlinks_created(&self) -> Vec<LockFileVerifyError> {
+        let mut errors = Vec::new();
 
-    pub fn verify_symlinks_created(&self) -> Result<(), LockFileVerifyError> {
-        // for (lock_link_name, _lock_link_target) in &self.symlinks {
-        for lock_link_name in self.symlinks.keys() {
-            let symlink_metadata = std::fs::symlink_metadata(lock_link_name).map_err(|error| {
-                if let io::ErrorKind::NotFound = error.kind() {
-                    LockFileVerifyError::SymlinkNotFound(lock_link_name.clone())
-                } else {
-                    LockFileVerifyError::IoError(error)
+        for (lock_link_name, _lock_link_target) in self.sorted_symlinks() {
+            match std::fs::symlink_metadata(lock_link_name) {
+                Ok(symlink_metadata) => {
+                    if !symlink_metadata.is_symlink() {
+                        errors.push(LockFileVerifyError::NotASymlink {
+                            link_name: lock_link_name.clone(),
+                        });
+                    }
                 }
-            })?;
-
-            if !symlink_metadata.is_symlink() {
-                return Err(LockFileVerifyError::NotASymlink(lock_link_name.clone()));
+                Err(error) => errors.push(if let io::ErrorKind::NotFound = error.kind() {
+                    LockFileVerifyError::SymlinkNotFound {
+                        link_name: lock_link_name.clone(),
+                    }
+                } else {
+                    LockFileVerifyError::IoError {
+                        link_name: lock_link_name.clone(),
+                        error,
+                    }
+                }),
             }
         }
 
-        Ok(())
+        errors
     }
 
-    pub fn verify_symlink_targets_exist(&self) -> Result<(), LockFileVerifyError> {
-        for (lock_link_name, lock_link_target) in &self.symlinks {
-            let symlink_target = lock_link_name.canonicalize().map_err(|error| {
-                if let io::ErrorKind::NotFound = error.kind() {
-                    LockFileVerifyError::SymlinkTargetNotFound {
+    /// Relative symlink targets are resolved from the symlink's parent directory
+    /// before being compared to the lock file. Symlinks that do not exist on
+    /// disk are skipped, they are reported by [`LockFile::verify_symlinks_created`]
+    pub fn verify_symlink_targets_exist(&self) -> Vec<LockFileVerifyError> {
+        let mut errors = Vec::new();
+
+        for (lock_link_name, lock_link_target) in self.sorted_symlinks() {
+            if !lock_link_name.is_symlink() {
+                continue;
+            }
+
+            let disk_link_target = match crate::path::read_link_absolute(lock_link_name) {
+                Ok(disk_link_target) => disk_link_target,
+                Err(error) => {
+                    errors.push(LockFileVerifyError::IoError {
                         link_name: lock_link_name.clone(),
-                        link_target: lock_link_target.clone(),
-                    }
-                } else {
-                    LockFileVerifyError::IoError(error)
+                        error,
+                    });
+                    continue;
                 }
-            })?;
-
-            let invalid_target_error = LockFileVerifyError::InvalidSymlinkTarget {
-                link_name: lock_link_name.clone(),
-                lock_file_link_target: lock_link_target.clone(),
-                disk_link_target: Some(symlink_target.clone()),
             };
-            if symlink_target.as_path() != lock_link_target.as_path() {
-                return Err(invalid_target_error);
+
+            if disk_link_target.as_path() != lock_link_target.as_path() {
+                errors.push(LockFileVerifyError::InvalidSymlinkTarget {
+                    link_name: lock_link_name.clone(),
+                    lock_file_link_target: lock_link_target.clone(),
+                    disk_link_target: Some(disk_link_target),
+                });
+            } else if !lock_link_target.exists() {
+                errors.push(LockFileVerifyError::SymlinkTargetNotFound {
+                    link_name: lock_link_name.clone(),
+                    link_target: lock_link_target.clone(),
+                });
             }
         }
 
-        Ok(())
+        errors
+    }
+
+    fn sorted_symlinks(&self) -> Vec<(&PathBuf, &PathBuf)> {
+        let mut symlinks: Vec<_> = self
+            .symlinks
+            .iter()
+            .map(|(name, link)| (name, &link.target))
+            .collect();
+        symlinks.sort();
+        symlinks
     }
 
     pub fn get_symlinks_to_delete(
         &self,
         config_symlinks: &HashMap<PathBuf, PathBuf>,
     ) -> Vec<(PathBuf, PathBuf)> {
-        self.symlinks
-            .iter()
+        self.sorted_symlinks()
+            .into_iter()
             .filter(|(lockfile_name, lockfile_target)| {
                 config_symlinks
                     .get(*lockfile_name)
@@ -254,17 +425,126 @@ impl LockFile {
             .collect()
     }
 
-    pub fn to_string(&self) -> Result<String, toml::ser::Error> {
-        toml::to_string(&self)
+    /// Serialize with paths written as set by [`LockFile::set_paths`]
+    pub fn to_string(&self) -> Result<String, WriteLockFileError> {
+        let lock_dir = self.lock_dir()?;
+        let home_dir = crate::path::get_home_dir().ok();
+        let store_path = |path: &Path| match self.paths {
+            LockPaths::Absolute => path.to_path_buf(),
+            LockPaths::Home => match home_dir
+                .as_deref()
+                .and_then(|home_dir| path.strip_prefix(home_dir).ok())
+            {
+                Some(path_in_home) => Path::new("~").join(path_in_home),
+                None => path.to_path_buf(),
+            },
+            LockPaths::LockDir => crate::path::relative_to(path, lock_dir.as_path()),
+        };
+
+        let stored = LockFile {
+            version: self.version,
+            hostname: self.hostname.clone(),
+            generated_at: self.generated_at.clone(),
+            paths: self.paths,
+            symlinks: self
+                .symlinks
+                .iter()
+                .map(|(name, link)| (store_path(name), link.map_paths(store_path)))
+                .collect(),
+            file_path: self.file_path.clone(),
+        };
+        Ok(toml::to_string(&stored)?)
+    }
+
+    /// Absolute directory of the lock file, base of relative paths
+    fn lock_dir(&self) -> io::Result<PathBuf> {
+        let file_path =
+            crate::path::normalize_path(std::path::absolute(self.file_path())?.as_path());
+        Ok(file_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or(file_path))
+    }
+
+    /// Resolve the paths read from the lock file: `~/` from the home
+    /// directory, relative paths from the directory of the lock file
+    fn resolve_paths(&mut self) -> io::Result<()> {
+        let lock_dir = self.lock_dir()?;
+        let home_dir = crate::path::get_home_dir().ok();
+        let resolve_path = |path: &Path| match (path.strip_prefix("~"), home_dir.as_deref()) {
+            (Ok(path_in_home), Some(home_dir)) => home_dir.join(path_in_home),
+            _ => crate::path::join(lock_dir.as_path(), path),
+        };
+
+        self.symlinks = std::mem::take(&mut self.symlinks)
+            .into_iter()
+            .map(|(name, link)| (resolve_path(&name), link.map_paths(resolve_path)))
+            .collect();
+        Ok(())
+    }
+
+    /// True if writing the lock file would not change it, apart from the
+    /// hostname and the time
+    pub fn is_up_to_date(&self) -> Result<bool, WriteLockFileError> {
+        match std::fs::read_to_string(self.file_path.as_path()) {
+            Ok(contents) => Ok(contents == self.to_string()?),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub fn file_path(&self) -> &Path {
+        self.file_path.as_path()
+    }
+
+    /// Write the lock file in the current format, stamped with the hostname
+    /// and the time. Nothing is written if the symlinks did not change.
+    pub fn write(&mut self) -> Result<(), WriteLockFileError> {
+        if self.is_up_to_date()? {
+            return Ok(());
+        }
+        self.version = Self::VERSION;
+        self.hostname = Some(gethostname::gethostname().to_string_lossy().into_owned());
+        self.generated_at = Some(format_timestamp(SystemTime::now()));
+        let serialized = self.to_string()?;
+
+        // readers never see a partially written lock file
+        let mut temp_path = OsString::from(self.file_path.as_os_str());
+        temp_path.push(".tmp");
+        let mut temp_file = File::create(temp_path.as_os_str())?;
+        temp_file.write_all(serialized.as_bytes())?;
+        temp_file.sync_all()?;
+        std::fs::rename(temp_path.as_os_str(), self.file_path.as_path())?;
+        Ok(())
     }
 }
 
 #[derive(Debug)]
 pub enum ParseLockFileError {
     Deserialize(toml::de::Error),
+    /// Written by a newer symlonk, or not a known version
+    UnsupportedVersion(i64),
     Io(io::Error),
 }
 
+impl Display for ParseLockFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseLockFileError::Deserialize(error) => error.fmt(f),
+            ParseLockFileError::UnsupportedVersion(version) if *version > LockFile::VERSION => f
+                .write_fmt(format_args!(
+                    "lock file version {} is newer than the supported version {}, upgrade symlonk",
+                    version,
+                    LockFile::VERSION
+                )),
+            ParseLockFileError::UnsupportedVersion(version) => {
+                f.write_fmt(format_args!("unknown lock file version {}", version))
+            }
+            ParseLockFileError::Io(error) => error.fmt(f),
+        }
+    }
+}
+
 impl From<io::Error> for ParseLockFileError {
     fn from(value: io::Error) -> Self {
         Self::Io(value)
@@ -277,65 +557,224 @@ impl From<toml::de::Error> for ParseLockFileError {
     }
 }
 
+#[derive(Debug)]
+pub enum WriteLockFileError {
+    Serialize(toml::ser::Error),
+    Io(io::Error),
+}
+
+impl Display for WriteLockFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteLockFileError::Serialize(error) => error.fmt(f),
+            WriteLockFileError::Io(error) => error.fmt(f),
+        }
+    }
+}
+
+impl From<io::Error> for WriteLockFileError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<toml::ser::Error> for WriteLockFileError {
+    fn from(value: toml::ser::Error) -> Self {
+        Self::Serialize(value)
+    }
+}
+
 pub fn parse_lock_file(file_path: &Path) -> Result<LockFile, ParseLockFileError> {
     let log = Logger::default();
     let lock_file_contents = std::fs::read_to_string(file_path)?;
-    let lock_file: LockFile = toml::from_str(lock_file_contents.as_str())?;
+    let header: LockFileHeader = toml::from_str(lock_file_contents.as_str())?;
+    let mut lock_file = match header.version {
+        None | Some(1) => {
+            let lock_file_v1: LockFileV1 = toml::from_str(lock_file_contents.as_str())?;
+            log.info(format_args!(
+                "upgrading lock file {} from version 1 to {}, it is rewritten in the new format on the next change",
+                file_path.to_string_lossy(),
+                LockFile::VERSION
+            ));
+            let mut lock_file = LockFile::new(file_path.to_path_buf());
+            lock_file.symlinks = lock_file_v1
+                .symlinks
+                .into_iter()
+                .map(|(name, target)| {
+                    let link = LockedLink {
+                        created_at: None,
+                        ..LockedLink::new(target)
+                    };
+                    (name, link)
+                })
+                .collect();
+            lock_file
+        }
+        Some(LockFile::VERSION) => toml::from_str(lock_file_contents.as_str())?,
+        Some(version) => return Err(ParseLockFileError::UnsupportedVersion(version)),
+    };
+    lock_file.file_path = file_path.to_path_buf();
+    lock_file.resolve_paths()?;
 
     log.debug(format_args!("parsed lock file: {:#?}", lock_file));
 
     Ok(lock_file)
 }
 
-pub fn verify(lock_file_path: &Path, config_files: Option<Vec<PathBuf>>) {
-    let log = Logger::default();
-    let lock_file = crate::lock::parse_lock_file(lock_file_path).unwrap_or_else(|error| {
-        log.error(format_args!("parse_lock_file: {:?}", error));
-        panic!();
-    });
-
-    if let Some(config_files) = config_files {
-        let config_symlinks = crate::config::parse_symlinks_from_config_files(&config_files)
-            .unwrap_or_else(|error| {
-                log.error(format_args!(
-                    "[parse_symlinks_from_config_files] {:?}",
-                    error
-                ));
-                panic!()
+/// Format as an RFC 3339 UTC timestamp with second precision
+fn format_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (days, seconds_of_day) = (seconds / 86400, seconds % 86400);
+
+    // civil from days, see https://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60
+    )
+}
+
+/// Problems found by [`verify`], grouped by check
+#[derive(Debug, Default, Serialize)]
+pub struct VerifyReport {
+    pub symlink_count: usize,
+
+    /// `None` if no config files were given
+    pub config_errors: Option<Vec<LockFileVerifyError>>,
+
+    pub created_errors: Vec<LockFileVerifyError>,
+
+    pub target_errors: Vec<LockFileVerifyError>,
+}
+
+impl VerifyReport {
+    pub fn error_count(&self) -> usize {
+        self.config_errors.as_ref().map_or(0, Vec::len)
+            + self.created_errors.len()
+            + self.target_errors.len()
+    }
+
+    /// Fails if any check found a problem
+    pub fn check(&self) -> SymlonkResult<()> {
+        if self.error_count() > 0 {
+            return Err(SymlonkError::Verify {
+                config_error_count: self.config_errors.as_ref().map_or(0, Vec::len),
+                created_error_count: self.created_errors.len(),
+                target_error_count: self.target_errors.len(),
             });
+        }
+        Ok(())
+    }
 
-        match lock_file.verify_config(&config_symlinks) {
-            Ok(_) => {
-                // todo!()
+    pub fn print(&self, log: &Logger) {
+        if let Some(config_errors) = &self.config_errors {
+            if config_errors.is_empty() {
                 log.info(format_args!("verify: config matches lock file"));
             }
-            Err(error) => {
+            for error in config_errors {
                 log.error(format_args!("verify: {}", error));
             }
         }
-    }
 
-    match lock_file.verify_symlinks_created() {
-        Ok(_) => {
+        if self.created_errors.is_empty() {
             log.info(format_args!(
                 "verify: {} symlinks from lock file are created",
-                lock_file.symlink_count()
+                self.symlink_count
             ));
         }
-        Err(error) => {
+        for error in &self.created_errors {
             log.error(format_args!("verify: {}", error));
         }
-    }
 
-    match lock_file.verify_symlink_targets_exist() {
-        Ok(_) => {
+        if self.target_errors.is_empty() {
             log.info(format_args!(
                 "verify: {} symlinks point to existing files",
-                lock_file.symlink_count()
+                self.symlink_count
             ));
         }
-        Err(error) => {
+        for error in &self.target_errors {
             log.error(format_args!("verify: {}", error));
         }
     }
 }
+
+pub fn verify(
+    lock_file_path: &Path,
+    config_files: Option<Vec<PathBuf>>,
+    allow_override: bool,
+    output: OutputFormat,
+) -> SymlonkResult<()> {
+    let report = verify_report(lock_file_path, config_files, allow_override)?;
+    match output {
+        OutputFormat::Text => report.print(&Logger::default()),
+        OutputFormat::Json => crate::output::print_json(&report)?,
+    }
+    report.check()
+}
+
+/// Run every check of [`verify`] without printing the result
+pub fn verify_report(
+    lock_file_path: &Path,
+    config_files: Option<Vec<PathBuf>>,
+    allow_override: bool,
+) -> SymlonkResult<VerifyReport> {
+    let lock_file = crate::lock::parse_lock_file(lock_file_path).map_err(|error| {
+        SymlonkError::ParseLockFile {
+            lock_file_path: lock_file_path.to_path_buf(),
+            error,
+        }
+    })?;
+
+    let config_errors = match config_files {
+        Some(config_files) => {
+            let config_symlinks =
+                crate::config::parse_symlinks_from_config_files(&config_files, allow_override)?;
+            Some(lock_file.verify_config(&config_symlinks))
+        }
+        None => None,
+    };
+
+    Ok(VerifyReport {
+        symlink_count: lock_file.symlink_count(),
+        config_errors,
+        created_errors: lock_file.verify_symlinks_created(),
+        target_errors: lock_file.verify_symlink_targets_exist(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn format_timestamps() {
+        let format = |seconds| format_timestamp(UNIX_EPOCH + Duration::from_secs(seconds));
+
+        assert_eq!(format(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format(951782400), "2000-02-29T00:00:00Z");
+        assert_eq!(format(4107542399), "2100-02-28T23:59:59Z");
+    }
+}
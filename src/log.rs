@@ -1,6 +1,18 @@
-use std::io::Write;
+use std::{
+    io::{IsTerminal, Write},
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+};
 
 use clap::builder::styling::{AnsiColor, Color, Reset, Style};
+use serde::Deserialize;
+
+static LOG_TO_STDERR: AtomicBool = AtomicBool::new(false);
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+/// Write logs and prompts to stderr, to keep stdout for machine-readable output
+pub fn log_to_stderr(enabled: bool) {
+    LOG_TO_STDERR.store(enabled, Ordering::Relaxed);
+}
 
 pub struct LoggerStyles {
     success: Style,
@@ -12,7 +24,13 @@ pub struct LoggerStyles {
     tag: Style,
 }
 
-#[derive(PartialEq, PartialOrd)]
+/// Level of the loggers created without an explicit level
+pub fn set_log_level(level: LogLevel) {
+    LOG_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum LogLevel {
     Debug = 0,
     Info,
@@ -21,6 +39,17 @@ pub enum LogLevel {
     // Off,
 }
 
+impl LogLevel {
+    fn from_u8(level: u8) -> Self {
+        match level {
+            0 => LogLevel::Debug,
+            1 => LogLevel::Info,
+            2 => LogLevel::Success,
+            _ => LogLevel::Error,
+        }
+    }
+}
+
 pub struct Logger {
     styles: LoggerStyles,
     level: LogLevel,
@@ -38,7 +67,7 @@ impl Logger {
 
                 tag: Style::new().bold(),
             }),
-            level: level.unwrap_or(LogLevel::Info),
+            level: level.unwrap_or_else(|| LogLevel::from_u8(LOG_LEVEL.load(Ordering::Relaxed))),
         }
     }
 
@@ -83,12 +112,18 @@ impl Logger {
     }
 
     pub fn prompt_char(&self, message: std::fmt::Arguments) -> std::io::Result<Option<char>> {
-        print!(
+        let prompt = format!(
             "{}[{}PROMPT{:#}{}] {}{:#} ",
             self.styles.prompt, self.styles.tag, Reset, self.styles.prompt, message, Reset,
         );
+        if LOG_TO_STDERR.load(Ordering::Relaxed) {
+            eprint!("{}", prompt);
+            std::io::stderr().flush()?;
+        } else {
+            print!("{}", prompt);
+            std::io::stdout().flush()?;
+        }
         let mut input_buf = String::new();
-        std::io::stdout().flush()?;
         std::io::stdin().read_line(&mut input_buf)?;
 
         Ok(input_buf.chars().next())
@@ -96,11 +131,20 @@ impl Logger {
 
     fn log(&self, level: LogLevel, message: std::fmt::Arguments) {
         if self.level <= level {
-            println!("{}", message);
+            if LOG_TO_STDERR.load(Ordering::Relaxed) {
+                eprintln!("{}", message);
+            } else {
+                println!("{}", message);
+            }
         }
     }
 }
 
+/// Whether the user can answer prompts on stdin
+pub fn is_interactive() -> bool {
+    std::io::stdin().is_terminal()
+}
+
 impl Default for Logger {
     // TODO: better log management, shouldn't create new logger everywhere
     fn default() -> Self {
@@ -1,11 +1,32 @@
+mod add;
 mod cli;
+mod condition;
 mod config;
+mod discover;
+mod error;
+mod flock;
+mod init;
+mod journal;
 mod link;
 mod lock;
 mod log;
+mod manifest;
+mod output;
 mod path;
+mod plan;
+mod remove;
 mod schema;
+mod status;
+mod unlink;
 
-fn main() {
-    cli::run()
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    match cli::run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            log::Logger::default().error(format_args!("{}", error));
+            error.exit_code()
+        }
+    }
 }
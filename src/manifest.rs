@@ -0,0 +1,116 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::{
+    config::{ParseConfigFileError, ParseConfigFileErrorKind, ParseConfigFileResult},
+    link::ConflictPolicy,
+    log::{LogLevel, Logger},
+};
+
+/// File name of the project manifest, looked up in the current directory and
+/// its ancestors. It does not end with `symlonk.toml`, so that it cannot be
+/// mistaken for a config file.
+pub const MANIFEST_FILE_NAME: &str = "symlonk-project.toml";
+
+/// Project settings used as defaults by every command. Paths are relative to
+/// the manifest directory.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Lock file used when `--lock-file` is omitted
+    lock_file: Option<PathBuf>,
+
+    /// Config files used when none are given on the command line
+    #[serde(default)]
+    config_files: Vec<PathBuf>,
+
+    /// Directories searched for config files like `--discover`, when no
+    /// config files are given on the command line
+    #[serde(default)]
+    config_dirs: Vec<PathBuf>,
+
+    /// Conflict policy used when `--on-conflict` is omitted
+    pub on_conflict: Option<ConflictPolicy>,
+
+    pub log_level: Option<LogLevel>,
+
+    #[serde(skip)]
+    file_path: PathBuf,
+}
+
+impl Manifest {
+    /// Parse the closest manifest in the current directory or its ancestors
+    pub fn find() -> ParseConfigFileResult<Option<Self>> {
+        let current_dir = std::env::current_dir()
+            .map_err(|error| ParseConfigFileError::new(error, Path::new(".")))?;
+
+        match current_dir
+            .ancestors()
+            .map(|dir| dir.join(MANIFEST_FILE_NAME))
+            .find(|path| path.is_file())
+        {
+            Some(path) => Self::parse(path.as_path()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn parse(path: &Path) -> ParseConfigFileResult<Self> {
+        let log = Logger::default();
+        let file_contents = std::fs::read_to_string(path)
+            .map_err(|error| ParseConfigFileError::new(error, path))?;
+        let mut manifest: Self = toml::from_str(file_contents.as_str())
+            .map_err(|error| ParseConfigFileError::new(error, path))?;
+        manifest.file_path = path.to_path_buf();
+
+        log.debug(format_args!(
+            "parsed manifest {} : {:#?}",
+            path.to_string_lossy(),
+            manifest
+        ));
+        Ok(manifest)
+    }
+
+    pub fn lock_file(&self) -> ParseConfigFileResult<Option<PathBuf>> {
+        self.lock_file
+            .as_deref()
+            .map(|lock_file| self.resolve(lock_file))
+            .transpose()
+    }
+
+    /// Config files, followed by the ones discovered in config directories
+    pub fn config_files(&self) -> ParseConfigFileResult<Vec<PathBuf>> {
+        let mut config_files = self
+            .config_files
+            .iter()
+            .map(|config_file| self.resolve(config_file))
+            .collect::<ParseConfigFileResult<Vec<_>>>()?;
+
+        for config_dir in &self.config_dirs {
+            let config_dir = self.resolve(config_dir)?;
+            for config_file in crate::discover::discover_config_files(config_dir.as_path())? {
+                if !config_files.contains(&config_file) {
+                    config_files.push(config_file);
+                }
+            }
+        }
+
+        Ok(config_files)
+    }
+
+    /// Expand `path` and make it relative to the manifest directory
+    fn resolve(&self, path: &Path) -> ParseConfigFileResult<PathBuf> {
+        let manifest_dir = self.file_path.parent().unwrap_or(Path::new("/"));
+        let expanded = crate::path::expand(path).map_err(|error| {
+            ParseConfigFileError::new(
+                ParseConfigFileErrorKind::InvalidPath {
+                    path: path.to_path_buf(),
+                    error,
+                },
+                self.file_path.as_path(),
+            )
+        })?;
+
+        Ok(crate::path::join(manifest_dir, expanded.as_path()))
+    }
+}
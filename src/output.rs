@@ -0,0 +1,93 @@
+use std::{fmt::Display, io::Write, path::PathBuf};
+
+use clap::ValueEnum;
+use serde::{Serialize, Serializer};
+
+use crate::lock::VerifyReport;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// Colored human readable text
+    #[default]
+    Text,
+    /// One JSON document on stdout, logs are written to stderr
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LinkEntry {
+    pub link_name: PathBuf,
+    pub link_target: PathBuf,
+}
+
+impl LinkEntry {
+    pub fn new(link_name: PathBuf, link_target: PathBuf) -> Self {
+        Self {
+            link_name,
+            link_target,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BackedUpLink {
+    pub link_name: PathBuf,
+    pub link_target: PathBuf,
+    pub backup_path: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FailedLink {
+    pub link_name: PathBuf,
+    pub error: String,
+}
+
+/// Result of `create links`
+#[derive(Debug, Default, Serialize)]
+pub struct ApplyReport {
+    pub pruned: Vec<LinkEntry>,
+    pub created: Vec<LinkEntry>,
+    pub already_linked: Vec<LinkEntry>,
+    pub overwritten: Vec<LinkEntry>,
+    pub backed_up: Vec<BackedUpLink>,
+    pub skipped: Vec<LinkEntry>,
+    pub failed: Vec<FailedLink>,
+
+    /// An error stopped the apply and its changes were undone
+    pub rolled_back: bool,
+}
+
+/// Result of `create links --verify`
+#[derive(Debug, Serialize)]
+pub struct ApplyVerifyReport<'a> {
+    pub apply: &'a ApplyReport,
+    pub verify: &'a VerifyReport,
+}
+
+/// Result of `unlink`
+#[derive(Debug, Default, Serialize)]
+pub struct UnlinkReport {
+    pub unlinked: Vec<LinkEntry>,
+
+    /// Symlinks that did not exist anymore, dropped from the lock file
+    pub missing: Vec<LinkEntry>,
+
+    /// Symlinks kept in the lock file
+    pub failed: Vec<FailedLink>,
+
+    /// Paths given on the command line that match no symlink of the lock file
+    pub unmatched: Vec<PathBuf>,
+}
+
+pub fn print_json<T: Serialize>(value: &T) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, value)?;
+    writeln!(stdout)
+}
+
+pub fn serialize_display<T: Display, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
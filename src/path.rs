@@ -1,4 +1,48 @@
-use std::{io, path::{Component, Path, PathBuf}};
+use std::{
+    fmt::Display,
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+/// Directories of the XDG base directory specification, used as
+/// `{xdg_config_home}` etc. in config paths: (token, variable, default
+/// relative to the home directory)
+const XDG_DIRS: [(&str, &str, &str); 4] = [
+    ("xdg_config_home", "XDG_CONFIG_HOME", ".config"),
+    ("xdg_data_home", "XDG_DATA_HOME", ".local/share"),
+    ("xdg_state_home", "XDG_STATE_HOME", ".local/state"),
+    ("xdg_cache_home", "XDG_CACHE_HOME", ".cache"),
+];
+
+#[derive(Debug)]
+pub enum ExpandPathError {
+    UndefinedVariable(String),
+    UnclosedVariable,
+    UnknownUser(String),
+    /// A `{name}` token that is not an XDG directory
+    UnknownToken(String),
+}
+
+impl Display for ExpandPathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExpandPathError::UndefinedVariable(name) => {
+                f.write_fmt(format_args!("environment variable {} is not defined", name))
+            }
+            ExpandPathError::UnclosedVariable => f.write_str("missing closing brace after \"${\""),
+            ExpandPathError::UnknownUser(user) => {
+                f.write_fmt(format_args!("no home directory found for user {}", user))
+            }
+            ExpandPathError::UnknownToken(token) => f.write_fmt(format_args!(
+                "unknown token {{{}}}, expected one of {}",
+                token,
+                XDG_DIRS
+                    .map(|(token, _, _)| format!("{{{}}}", token))
+                    .join(", ")
+            )),
+        }
+    }
+}
 
 pub fn path_exists<P: AsRef<Path>>(path: P) -> io::Result<bool> {
     let symlink_metadata =
@@ -32,17 +76,7 @@ pub fn normalize_path(path: &Path) -> PathBuf {
                 ret.pop();
             }
             Component::Normal(c) => {
-                if c == "~" {
-                    // if let Some(home_dir) = get_home_dir() {
-                    //     ret = home_dir;
-                    // }
-                    match get_home_dir() {
-                        Some(home_dir) => ret = home_dir,
-                        None => ret.push(c),
-                    }
-                } else {
-                    ret.push(c);
-                }
+                ret.push(c);
             }
         }
     }
@@ -53,6 +87,276 @@ pub fn join(first: &Path, second: &Path) -> PathBuf {
     crate::path::normalize_path(first.join(second).as_path())
 }
 
-fn get_home_dir() -> Option<PathBuf> {
-    std::env::var("HOME").map(PathBuf::from).ok()
+/// Target of the symlink at `link_name`, with relative targets resolved from
+/// the symlink's parent directory
+pub fn read_link_absolute(link_name: &Path) -> io::Result<PathBuf> {
+    let target = link_name.read_link()?;
+
+    Ok(match link_name.parent() {
+        Some(parent_path) => join(parent_path, target.as_path()),
+        None => target,
+    })
+}
+
+/// Path of `path` relative to `base`, both absolute and normalized
+pub fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    let mut path_components = path.components().peekable();
+    let mut base_components = base.components().peekable();
+
+    while let (Some(a), Some(b)) = (path_components.peek(), base_components.peek()) {
+        if a != b {
+            break;
+        }
+        path_components.next();
+        base_components.next();
+    }
+
+    let mut relative = PathBuf::new();
+    for _ in base_components {
+        relative.push(Component::ParentDir);
+    }
+    relative.extend(path_components);
+
+    if relative.as_os_str().is_empty() {
+        relative.push(Component::CurDir);
+    }
+    relative
+}
+
+/// Expand a leading `~` or `~user`, `$VAR`, `${VAR}`, `${VAR:-default}` and
+/// `{xdg_config_home}`-like tokens. `$$` is a literal `$`.
+pub fn expand(path: &Path) -> Result<PathBuf, ExpandPathError> {
+    let Some(path_str) = path.to_str() else {
+        return Ok(path.to_path_buf());
+    };
+
+    let (home_dir, rest) = match path_str.strip_prefix('~') {
+        Some(after_tilde) => {
+            let (user, rest) = match after_tilde.find('/') {
+                Some(index) => after_tilde.split_at(index),
+                None => (after_tilde, ""),
+            };
+            let home_dir = if user.is_empty() {
+                get_home_dir()?
+            } else {
+                get_user_home_dir(user)?
+            };
+            (Some(home_dir), rest)
+        }
+        None => (None, path_str),
+    };
+
+    let expanded = expand_variables(rest)?;
+    Ok(match home_dir {
+        Some(home_dir) => PathBuf::from(format!("{}{}", home_dir.to_string_lossy(), expanded)),
+        None => PathBuf::from(expanded),
+    })
+}
+
+fn expand_variables(value: &str) -> Result<String, ExpandPathError> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(index) = rest.find(['$', '{']) {
+        expanded.push_str(&rest[..index]);
+        rest = &rest[index..];
+
+        if let Some(after_dollar) = rest.strip_prefix('$') {
+            if let Some(after_dollar) = after_dollar.strip_prefix('$') {
+                expanded.push('$');
+                rest = after_dollar;
+            } else if let Some(after_brace) = after_dollar.strip_prefix('{') {
+                let end = closing_brace(after_brace).ok_or(ExpandPathError::UnclosedVariable)?;
+                let variable = &after_brace[..end];
+                let value = match variable.split_once(":-") {
+                    Some((name, default)) => match get_env_var(name) {
+                        Some(value) if !value.is_empty() => value,
+                        _ => expand(Path::new(default))?.to_string_lossy().to_string(),
+                    },
+                    None => get_env_var(variable)
+                        .ok_or(ExpandPathError::UndefinedVariable(variable.to_string()))?,
+                };
+                expanded.push_str(value.as_str());
+                rest = &after_brace[end + 1..];
+            } else {
+                let name_len = after_dollar
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after_dollar.len());
+                let name = &after_dollar[..name_len];
+                if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+                    // not a variable, the characters after `$` are kept
+                    expanded.push('$');
+                    rest = after_dollar;
+                } else {
+                    let value = get_env_var(name)
+                        .ok_or(ExpandPathError::UndefinedVariable(name.to_string()))?;
+                    expanded.push_str(value.as_str());
+                    rest = &after_dollar[name_len..];
+                }
+            }
+        } else {
+            let xdg_dir = XDG_DIRS.iter().find(|(token, _, _)| {
+                rest[1..]
+                    .strip_prefix(token)
+                    .is_some_and(|after_token| after_token.starts_with('}'))
+            });
+            match xdg_dir {
+                Some((token, variable, default)) => {
+                    let dir = match get_env_var(variable) {
+                        Some(dir) if Path::new(dir.as_str()).is_absolute() => PathBuf::from(dir),
+                        _ => get_home_dir()?.join(default),
+                    };
+                    expanded.push_str(dir.to_string_lossy().as_ref());
+                    rest = &rest[token.len() + 2..];
+                }
+                None => {
+                    let token_len = rest[1..]
+                        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                        .filter(|&len| len > 0 && rest[1 + len..].starts_with('}'));
+                    if let Some(token_len) = token_len {
+                        return Err(ExpandPathError::UnknownToken(
+                            rest[1..1 + token_len].to_string(),
+                        ));
+                    }
+                    expanded.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+    }
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+/// Index of the `}` closing a `${`, skipping nested braces of the default
+fn closing_brace(after_brace: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (index, c) in after_brace.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(index),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn get_env_var(name: &str) -> Option<String> {
+    std::env::var_os(name).map(|value| value.to_string_lossy().to_string())
+}
+
+pub fn get_home_dir() -> Result<PathBuf, ExpandPathError> {
+    get_env_var("HOME")
+        .map(PathBuf::from)
+        .ok_or(ExpandPathError::UndefinedVariable("HOME".to_string()))
+}
+
+/// Home directory of `user`, as found in /etc/passwd
+fn get_user_home_dir(user: &str) -> Result<PathBuf, ExpandPathError> {
+    let passwd = std::fs::read_to_string("/etc/passwd").unwrap_or_default();
+
+    passwd
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.len() > 5 && fields[0] == user)
+        .map(|fields| PathBuf::from(fields[5]))
+        .ok_or(ExpandPathError::UnknownUser(user.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Not defined in the test environment
+    const UNSET: &str = "SYMLONK_TEST_UNSET_VARIABLE";
+
+    fn expand_str(path: &str) -> Result<PathBuf, ExpandPathError> {
+        expand(Path::new(path))
+    }
+
+    fn xdg_config_home() -> PathBuf {
+        match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if Path::new(&dir).is_absolute() => PathBuf::from(dir),
+            _ => get_home_dir().unwrap().join(".config"),
+        }
+    }
+
+    #[test]
+    fn expand_default_with_nested_token() {
+        assert_eq!(
+            expand_str(format!("${{{}:-{{xdg_config_home}}/x}}", UNSET).as_str()).unwrap(),
+            xdg_config_home().join("x")
+        );
+        assert_eq!(
+            expand_str(format!("${{{}:-{{xdg_config_home}}}}/app", UNSET).as_str()).unwrap(),
+            xdg_config_home().join("app")
+        );
+    }
+
+    #[test]
+    fn expand_dollar_escape() {
+        assert_eq!(expand_str("a/$$b").unwrap(), PathBuf::from("a/$b"));
+        assert_eq!(expand_str("$$HOME").unwrap(), PathBuf::from("$HOME"));
+        assert_eq!(expand_str("a/$1").unwrap(), PathBuf::from("a/$1"));
+    }
+
+    #[test]
+    fn expand_home() {
+        assert_eq!(
+            expand_str("~/x").unwrap(),
+            get_home_dir().unwrap().join("x")
+        );
+        assert_eq!(
+            expand_str("~root/x").unwrap(),
+            get_user_home_dir("root").unwrap().join("x")
+        );
+        assert!(matches!(
+            expand_str("~symlonk-no-such-user/x"),
+            Err(ExpandPathError::UnknownUser(user)) if user == "symlonk-no-such-user"
+        ));
+    }
+
+    #[test]
+    fn expand_unknown_token() {
+        assert!(matches!(
+            expand_str("{xdg_nope_home}/x"),
+            Err(ExpandPathError::UnknownToken(token)) if token == "xdg_nope_home"
+        ));
+        // not a token
+        assert_eq!(expand_str("a{b/c}").unwrap(), PathBuf::from("a{b/c}"));
+        assert_eq!(expand_str("a{").unwrap(), PathBuf::from("a{"));
+    }
+
+    #[test]
+    fn expand_unclosed_variable() {
+        assert!(matches!(
+            expand_str("${HOME/x"),
+            Err(ExpandPathError::UnclosedVariable)
+        ));
+        assert!(matches!(
+            expand_str(format!("${{{}:-{{xdg_config_home}}/x", UNSET).as_str()),
+            Err(ExpandPathError::UnclosedVariable)
+        ));
+    }
+
+    #[test]
+    fn expand_undefined_variable() {
+        assert!(matches!(
+            expand_str(format!("${}/x", UNSET).as_str()),
+            Err(ExpandPathError::UndefinedVariable(name)) if name == UNSET
+        ));
+    }
+
+    #[test]
+    fn relative_to_paths() {
+        let relative = |path: &str, base: &str| relative_to(Path::new(path), Path::new(base));
+
+        assert_eq!(relative("/a/b", "/a/b"), PathBuf::from("."));
+        assert_eq!(relative("/a/b", "/c/d"), PathBuf::from("../../a/b"));
+        assert_eq!(relative("/a/b/c", "/a"), PathBuf::from("b/c"));
+        assert_eq!(relative("/a", "/a/b/c"), PathBuf::from("../.."));
+        assert_eq!(relative("/a/b", "/a/c"), PathBuf::from("../b"));
+    }
 }
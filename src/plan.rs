@@ -0,0 +1,183 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    io,
+    path::PathBuf,
+};
+
+use serde::Serialize;
+
+use crate::{
+    config::SymlinkDeclaration,
+    link::{self, ConflictPolicy, LinkPlan},
+    lock::LockFile,
+    log::Logger,
+    output::LinkEntry,
+};
+
+#[derive(Debug, Serialize)]
+pub struct PlannedConflict {
+    pub link_name: PathBuf,
+    pub link_target: PathBuf,
+    pub current_target: PathBuf,
+
+    /// Prompt if `None`
+    pub resolution: Option<ConflictPolicy>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BlockedLink {
+    pub link_name: PathBuf,
+
+    /// Parent path that is not a directory
+    pub parent: PathBuf,
+}
+
+/// Every action `create links` would take, computed without modifying the
+/// file system or the lock file
+#[derive(Debug, Default, Serialize)]
+pub struct Plan {
+    /// Symlinks and their lock file target
+    pub prune: Vec<LinkEntry>,
+
+    pub create: Vec<LinkEntry>,
+
+    pub already_linked: Vec<LinkEntry>,
+
+    pub conflicts: Vec<PlannedConflict>,
+
+    pub blocked: Vec<BlockedLink>,
+
+    pub create_dirs: BTreeSet<PathBuf>,
+}
+
+impl Plan {
+    pub fn new(
+        config_symlinks: &HashMap<PathBuf, SymlinkDeclaration>,
+        lock_file: &LockFile,
+        prune: bool,
+        on_conflict: Option<ConflictPolicy>,
+    ) -> io::Result<Self> {
+        let mut plan = Plan::default();
+
+        if prune {
+            plan.prune = lock_file
+                .get_symlinks_to_delete(&crate::config::symlink_targets(config_symlinks))
+                .into_iter()
+                .map(|(name, target)| LinkEntry::new(name, target))
+                .collect();
+        }
+        let pruned: HashSet<(&PathBuf, &PathBuf)> = plan
+            .prune
+            .iter()
+            .map(|entry| (&entry.link_name, &entry.link_target))
+            .collect();
+
+        for (name, declaration) in config_symlinks {
+            let target = &declaration.target;
+            match link::plan_link(name.as_path(), target.as_path())? {
+                LinkPlan::AlreadyLinked => plan
+                    .already_linked
+                    .push(LinkEntry::new(name.clone(), target.clone())),
+                // the existing link would be removed by prune before being created again
+                LinkPlan::Conflict { current_target }
+                    if pruned.contains(&(name, &current_target)) =>
+                {
+                    plan.create
+                        .push(LinkEntry::new(name.clone(), target.clone()))
+                }
+                LinkPlan::Conflict { current_target } => plan.conflicts.push(PlannedConflict {
+                    link_name: name.clone(),
+                    link_target: target.clone(),
+                    current_target,
+                    resolution: declaration.options.on_conflict.or(on_conflict),
+                }),
+                LinkPlan::Create { missing_dirs } => {
+                    plan.create_dirs.extend(missing_dirs);
+                    plan.create
+                        .push(LinkEntry::new(name.clone(), target.clone()))
+                }
+                LinkPlan::ParentNotADirectory(parent) => plan.blocked.push(BlockedLink {
+                    link_name: name.clone(),
+                    parent,
+                }),
+            }
+        }
+
+        plan.prune.sort_by(|a, b| a.link_name.cmp(&b.link_name));
+        plan.create.sort_by(|a, b| a.link_name.cmp(&b.link_name));
+        plan.already_linked
+            .sort_by(|a, b| a.link_name.cmp(&b.link_name));
+        plan.conflicts.sort_by(|a, b| a.link_name.cmp(&b.link_name));
+        plan.blocked.sort_by(|a, b| a.link_name.cmp(&b.link_name));
+
+        Ok(plan)
+    }
+
+    pub fn print(&self, log: &Logger) {
+        log.info(format_args!("dry run: no changes will be made"));
+
+        log.info(format_args!("prune ({}):", self.prune.len()));
+        for entry in &self.prune {
+            log.info(format_args!(
+                "  unlink {} (was {})",
+                entry.link_name.to_string_lossy(),
+                entry.link_target.to_string_lossy()
+            ));
+        }
+
+        log.info(format_args!(
+            "create directories ({}):",
+            self.create_dirs.len()
+        ));
+        for dir in &self.create_dirs {
+            log.info(format_args!("  {}", dir.to_string_lossy()));
+        }
+
+        log.info(format_args!("create ({}):", self.create.len()));
+        for entry in &self.create {
+            log.info(format_args!(
+                "  link {} to {}",
+                entry.link_name.to_string_lossy(),
+                entry.link_target.to_string_lossy()
+            ));
+        }
+
+        log.info(format_args!(
+            "already linked ({}):",
+            self.already_linked.len()
+        ));
+        for entry in &self.already_linked {
+            log.info(format_args!(
+                "  {} -> {}",
+                entry.link_name.to_string_lossy(),
+                entry.link_target.to_string_lossy()
+            ));
+        }
+
+        log.info(format_args!("conflicts ({}):", self.conflicts.len()));
+        for conflict in &self.conflicts {
+            let resolution = match conflict.resolution {
+                Some(policy) => policy.to_string(),
+                None => "prompt".to_string(),
+            };
+            log.info(format_args!(
+                "  {} exists (currently {}, trying to link to {}): {}",
+                conflict.link_name.to_string_lossy(),
+                conflict.current_target.to_string_lossy(),
+                conflict.link_target.to_string_lossy(),
+                resolution
+            ));
+        }
+
+        if !self.blocked.is_empty() {
+            log.error(format_args!("blocked ({}):", self.blocked.len()));
+            for blocked in &self.blocked {
+                log.error(format_args!(
+                    "  {}: parent is not a directory: {}",
+                    blocked.link_name.to_string_lossy(),
+                    blocked.parent.to_string_lossy()
+                ));
+            }
+        }
+    }
+}
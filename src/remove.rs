@@ -0,0 +1,266 @@
+use std::{
+    ffi::OsString,
+    fmt::Display,
+    io,
+    os::unix,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{
+    error::{SymlonkError, SymlonkResult},
+    log::Logger,
+};
+
+#[derive(Debug)]
+pub enum RemoveError {
+    NotASymlink(PathBuf),
+    NotInLockFile {
+        link_name: PathBuf,
+        lock_file_path: PathBuf,
+    },
+    /// The symlink comes from a glob, recursive or inherited entry that also
+    /// declares other symlinks
+    NoSingleEntry {
+        link_name: PathBuf,
+        config_file: PathBuf,
+    },
+    EditConfig {
+        config_file: PathBuf,
+        error: toml_edit::TomlError,
+    },
+}
+
+impl Display for RemoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RemoveError::NotASymlink(path) => {
+                f.write_fmt(format_args!("{} is not a symlink", path.to_string_lossy()))
+            }
+            RemoveError::NotInLockFile {
+                link_name,
+                lock_file_path,
+            } => f.write_fmt(format_args!(
+                "{} is not managed by lock file {}",
+                link_name.to_string_lossy(),
+                lock_file_path.to_string_lossy()
+            )),
+            RemoveError::NoSingleEntry {
+                link_name,
+                config_file,
+            } => f.write_fmt(format_args!(
+                "{} is not declared by its own entry in {}, edit the config file by hand",
+                link_name.to_string_lossy(),
+                config_file.to_string_lossy()
+            )),
+            RemoveError::EditConfig { config_file, error } => f.write_fmt(format_args!(
+                "could not edit config file {}: {}",
+                config_file.to_string_lossy(),
+                error
+            )),
+        }
+    }
+}
+
+/// Symlink replaced by `remove`
+#[derive(Debug, Serialize)]
+pub struct RemoveReport {
+    pub link_name: PathBuf,
+    pub link_target: PathBuf,
+
+    /// Config file whose entry was removed, if any declared the symlink
+    pub config_file: Option<PathBuf>,
+}
+
+/// Replace the managed symlink `path` with a copy of its target, then remove
+/// its entry from the declaring config file, if any, and from the lock file.
+/// The symlink and the config file are restored if a step fails.
+pub fn remove(
+    path: &Path,
+    config_files: &[PathBuf],
+    lock_file_path: &Path,
+) -> SymlonkResult<RemoveReport> {
+    let log = Logger::default();
+    let link_name = crate::path::join(std::env::current_dir()?.as_path(), path);
+    if !link_name.is_symlink() {
+        return Err(RemoveError::NotASymlink(link_name).into());
+    }
+    let disk_target = link_name.read_link()?;
+    let link_target = crate::path::read_link_absolute(link_name.as_path())?;
+
+    let mut lock_file = crate::lock::parse_lock_file(lock_file_path).map_err(|error| {
+        SymlonkError::ParseLockFile {
+            lock_file_path: lock_file_path.to_path_buf(),
+            error,
+        }
+    })?;
+    if lock_file.remove_symlink(link_name.as_path()).is_none() {
+        return Err(RemoveError::NotInLockFile {
+            link_name,
+            lock_file_path: lock_file_path.to_path_buf(),
+        }
+        .into());
+    }
+
+    let declarations = crate::config::parse_symlink_declarations(config_files, true)?;
+    let config_edit = match declarations.get(&link_name) {
+        Some(declaration) => {
+            let config_file = declaration.provenance.config_file.clone();
+            let contents = std::fs::read_to_string(config_file.as_path())?;
+            let edited = remove_symlink_entry(config_file.as_path(), &contents, &link_name)?;
+            Some((config_file, contents, edited))
+        }
+        None => {
+            log.info(format_args!(
+                "{} is not declared in config",
+                link_name.to_string_lossy()
+            ));
+            None
+        }
+    };
+
+    replace_with_copy(link_name.as_path(), link_target.as_path())?;
+    log.success(format_args!(
+        "replace {} with a copy of {}",
+        link_name.to_string_lossy(),
+        link_target.to_string_lossy()
+    ));
+
+    let restore_symlink = || {
+        let result = remove_all(link_name.as_path())
+            .and_then(|()| unix::fs::symlink(disk_target.as_path(), link_name.as_path()));
+        match result {
+            Ok(()) => log.info(format_args!(
+                "rollback: restore symlink {}",
+                link_name.to_string_lossy()
+            )),
+            Err(error) => log.error(format_args!(
+                "rollback: restore symlink {}: {}",
+                link_name.to_string_lossy(),
+                error
+            )),
+        }
+    };
+
+    if let Some((config_file, _, edited)) = &config_edit {
+        if let Err(error) = std::fs::write(config_file, edited) {
+            restore_symlink();
+            return Err(error.into());
+        }
+        log.success(format_args!(
+            "remove {} from {}",
+            link_name.to_string_lossy(),
+            config_file.to_string_lossy()
+        ));
+    }
+
+    if let Err(error) = lock_file.write() {
+        if let Some((config_file, contents, _)) = &config_edit {
+            match std::fs::write(config_file, contents) {
+                Ok(()) => log.info(format_args!(
+                    "rollback: restore {}",
+                    config_file.to_string_lossy()
+                )),
+                Err(error) => log.error(format_args!(
+                    "rollback: restore {}: {}",
+                    config_file.to_string_lossy(),
+                    error
+                )),
+            }
+        }
+        restore_symlink();
+        return Err(SymlonkError::WriteLockFile {
+            lock_file_path: lock_file_path.to_path_buf(),
+            error,
+        });
+    }
+
+    Ok(RemoveReport {
+        link_name,
+        link_target,
+        config_file: config_edit.map(|(config_file, _, _)| config_file),
+    })
+}
+
+/// Remove the `[symlinks]` entry of `config_file` that resolves to
+/// `link_name`, keeping the formatting and comments of the document
+fn remove_symlink_entry(
+    config_file: &Path,
+    contents: &str,
+    link_name: &Path,
+) -> SymlonkResult<String> {
+    let destination_dir = crate::config::parse_config_file(config_file)?
+        .config
+        .destination_dir;
+    let no_single_entry = || RemoveError::NoSingleEntry {
+        link_name: link_name.to_path_buf(),
+        config_file: config_file.to_path_buf(),
+    };
+
+    let mut document: toml_edit::DocumentMut =
+        contents.parse().map_err(|error| RemoveError::EditConfig {
+            config_file: config_file.to_path_buf(),
+            error,
+        })?;
+    let symlinks = document
+        .get_mut("symlinks")
+        .and_then(|symlinks| symlinks.as_table_like_mut())
+        .ok_or_else(no_single_entry)?;
+    let key = symlinks
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .find(|key| {
+            crate::path::expand(Path::new(key)).is_ok_and(|name| {
+                crate::path::join(destination_dir.as_path(), name.as_path()) == link_name
+            })
+        })
+        .ok_or_else(no_single_entry)?;
+    symlinks.remove(key.as_str());
+
+    Ok(document.to_string())
+}
+
+/// Copy `link_target` next to the symlink, then move the copy over it
+fn replace_with_copy(link_name: &Path, link_target: &Path) -> io::Result<()> {
+    let mut copy_name = OsString::from(".");
+    copy_name.push(link_name.file_name().unwrap_or_default());
+    copy_name.push(".symlonk-copy");
+    let copy_path = link_name.with_file_name(copy_name);
+
+    if let Err(error) = copy_all(link_target, copy_path.as_path()) {
+        let _ = remove_all(copy_path.as_path());
+        return Err(error);
+    }
+    if copy_path.is_dir() {
+        // a directory cannot be renamed over a symlink
+        std::fs::remove_file(link_name)?;
+    }
+    std::fs::rename(copy_path.as_path(), link_name)
+}
+
+/// Copy a file, or a directory and its content. Symlinks are copied as
+/// symlinks.
+fn copy_all(from: &Path, to: &Path) -> io::Result<()> {
+    let metadata = std::fs::symlink_metadata(from)?;
+    if metadata.is_symlink() {
+        unix::fs::symlink(from.read_link()?, to)
+    } else if metadata.is_dir() {
+        std::fs::create_dir(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy_all(entry.path().as_path(), to.join(entry.file_name()).as_path())?;
+        }
+        std::fs::set_permissions(to, metadata.permissions())
+    } else {
+        std::fs::copy(from, to).map(|_| ())
+    }
+}
+
+fn remove_all(path: &Path) -> io::Result<()> {
+    if std::fs::symlink_metadata(path)?.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
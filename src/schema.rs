@@ -50,6 +50,9 @@ pub struct JsonSchema {
 
     #[serde(rename = "oneOf", skip_serializing_if = "Option::is_none")]
     one_of: Option<Vec<JsonSchema>>,
+
+    #[serde(rename = "enum", default, skip_serializing_if = "Option::is_none")]
+    enum_values: Option<Vec<String>>,
 }
 
 impl JsonSchema {
@@ -64,9 +67,15 @@ impl JsonSchema {
             title: String::from(title),
             description: String::from(description),
             one_of,
+            enum_values: None,
         }
     }
 
+    pub fn with_enum(mut self, values: &[&str]) -> Self {
+        self.enum_values = Some(values.iter().map(|value| value.to_string()).collect());
+        self
+    }
+
     pub fn one_of(typed_schemas: Vec<TypedJsonSchema>, title: &str, description: &str) -> Self {
         let schemas = typed_schemas.into_iter().map(JsonSchema::from).collect();
 
@@ -75,6 +84,7 @@ impl JsonSchema {
             title: title.to_string(),
             description: description.to_string(),
             one_of: Some(schemas),
+            enum_values: None,
         }
     }
 }
@@ -100,6 +110,150 @@ pub struct JsonSchemaDocument {
     json_schema: JsonSchema,
 }
 
+fn symlink_entry_schema() -> JsonSchema {
+    JsonSchema::new(
+        None,
+        "Maps symlink name to target path relative to the configured source directory.",
+        "Either a target path (which may be a glob pattern, whose matches are linked inside the symlink name directory), a table of symlink options, or false to remove inherited symlinks.",
+        Some(vec![
+            JsonSchema::new(
+                Some(TypedJsonSchema::String),
+                "Symlink target",
+                "Target path or glob pattern relative to the configured source directory",
+                None,
+            ),
+            JsonSchema::new(
+                Some(TypedJsonSchema::Boolean),
+                "Remove symlink",
+                "false removes the symlinks inherited from extends at or under the symlink name",
+                None,
+            ),
+            JsonSchema::new(
+                Some(TypedJsonSchema::Object {
+                    additional_properties: false,
+                    pattern_properties: None,
+                    properties: Some(HashMap::from([
+                        (
+                            "target".to_string(),
+                            JsonSchema::new(
+                                Some(TypedJsonSchema::String),
+                                "Symlink target",
+                                "Target path or glob pattern relative to the configured source directory",
+                                None,
+                            ),
+                        ),
+                        (
+                            "recursive".to_string(),
+                            JsonSchema::new(
+                                Some(TypedJsonSchema::Boolean),
+                                "Recursive",
+                                "Link every file under the target directory inside the symlink name directory, instead of linking the directory itself",
+                                None,
+                            ),
+                        ),
+                        (
+                            "relative".to_string(),
+                            JsonSchema::new(
+                                Some(TypedJsonSchema::Boolean),
+                                "Relative",
+                                "Point the symlink to a path relative to its parent directory",
+                                None,
+                            ),
+                        ),
+                        (
+                            "mode".to_string(),
+                            JsonSchema::new(
+                                Some(TypedJsonSchema::String),
+                                "Mode",
+                                "Octal permissions set on the symlink target, e.g. \"0755\"",
+                                None,
+                            ),
+                        ),
+                        (
+                            "on_conflict".to_string(),
+                            JsonSchema::new(
+                                Some(TypedJsonSchema::String),
+                                "On conflict",
+                                "What to do when a file already exists at the symlink path, overrides --on-conflict",
+                                None,
+                            )
+                            .with_enum(&["skip", "overwrite", "backup", "fail"]),
+                        ),
+                        (
+                            "if_exists".to_string(),
+                            JsonSchema::new(
+                                Some(TypedJsonSchema::Boolean),
+                                "If exists",
+                                "Only create the symlink if the target exists",
+                                None,
+                            ),
+                        ),
+                        (
+                            "when".to_string(),
+                            condition_schema("Only create the symlink if every given field matches"),
+                        ),
+                    ])),
+                    required: Some(vec!["target".to_string()]),
+                }),
+                "Symlink options",
+                "",
+                None,
+            ),
+        ]),
+    )
+}
+
+fn condition_schema(description: &str) -> JsonSchema {
+    JsonSchema::new(
+        Some(TypedJsonSchema::Object {
+            additional_properties: false,
+            pattern_properties: None,
+            properties: Some(HashMap::from([
+                (
+                    "hostname".to_string(),
+                    JsonSchema::new(
+                        Some(TypedJsonSchema::String),
+                        "Hostname",
+                        "Glob pattern matched against the hostname",
+                        None,
+                    ),
+                ),
+                (
+                    "os".to_string(),
+                    JsonSchema::new(
+                        Some(TypedJsonSchema::String),
+                        "Operating system",
+                        "Operating system, e.g. \"linux\" or \"macos\"",
+                        None,
+                    ),
+                ),
+                (
+                    "arch".to_string(),
+                    JsonSchema::new(
+                        Some(TypedJsonSchema::String),
+                        "Architecture",
+                        "CPU architecture, e.g. \"x86_64\" or \"aarch64\"",
+                        None,
+                    ),
+                ),
+                (
+                    "env".to_string(),
+                    JsonSchema::new(
+                        Some(TypedJsonSchema::String),
+                        "Environment variable",
+                        "\"VAR\" if the variable must be set, \"VAR=value\" if it must have a given value",
+                        None,
+                    ),
+                ),
+            ])),
+            required: None,
+        }),
+        "Condition",
+        description,
+        None,
+    )
+}
+
 pub fn to_writer<W: std::io::Write>(writer: W) -> serde_json::Result<()> {
     let json_schema_document = JsonSchemaDocument {
         id: None,
@@ -136,6 +290,15 @@ pub fn to_writer<W: std::io::Write>(writer: W) -> serde_json::Result<()> {
                                                 None,
                                             ),
                                         ),
+                                        (
+                                            "relative".to_string(),
+                                            JsonSchema::new(
+                                                Some(TypedJsonSchema::Boolean),
+                                                "Relative symlinks",
+                                                "Point symlinks to paths relative to their parent directory, unless overridden per symlink",
+                                                None,
+                                            ),
+                                        ),
                                     ])),
                                     required: Some(vec![
                                         "source_dir".to_string(),
@@ -149,10 +312,30 @@ pub fn to_writer<W: std::io::Write>(writer: W) -> serde_json::Result<()> {
                                         (
                                             "extends".to_string(),
                                             JsonSchema::new(
-                                                Some(TypedJsonSchema::String),
-                                                "Parent configuration",
-                                                "Inherit default configuration values from another configuration file.",
                                                 None,
+                                                "Parent configurations",
+                                                "Inherit configuration values and symlinks from one or more configuration files. Symlinks of later parents override those of earlier ones, and configuration values are inherited from the last parent.",
+                                                Some(vec![
+                                                    JsonSchema::new(
+                                                        Some(TypedJsonSchema::String),
+                                                        "Parent configuration",
+                                                        "Path to the parent configuration file",
+                                                        None,
+                                                    ),
+                                                    JsonSchema::new(
+                                                        Some(TypedJsonSchema::Array {
+                                                            items: Box::new(JsonSchema::new(
+                                                                Some(TypedJsonSchema::String),
+                                                                "Parent configuration",
+                                                                "Path to a parent configuration file",
+                                                                None,
+                                                            )),
+                                                        }),
+                                                        "Parent configurations",
+                                                        "Paths to parent configuration files, in merge order",
+                                                        None,
+                                                    ),
+                                                ]),
                                             ),
                                         ),
                                         (
@@ -173,6 +356,15 @@ pub fn to_writer<W: std::io::Write>(writer: W) -> serde_json::Result<()> {
                                                 None,
                                             ),
                                         ),
+                                        (
+                                            "relative".to_string(),
+                                            JsonSchema::new(
+                                                Some(TypedJsonSchema::Boolean),
+                                                "Relative symlinks",
+                                                "Point symlinks to paths relative to their parent directory, unless overridden per symlink",
+                                                None,
+                                            ),
+                                        ),
                                     ])),
                                     required: Some(vec!["extends".to_string()]),
                                 },
@@ -187,13 +379,8 @@ pub fn to_writer<W: std::io::Write>(writer: W) -> serde_json::Result<()> {
                             Some(TypedJsonSchema::Object {
                                 additional_properties: false,
                                 pattern_properties: Some(HashMap::from([(
-                                    String::from(r"^[a-zA-Z0-9_./-]+$"),
-                                    JsonSchema::new(
-                                        Some(TypedJsonSchema::String),
-                                        "Maps symlink name to target path relative to the configured source directory.",
-                                        "",
-                                        None,
-                                    ),
+                                    String::from(r"^[a-zA-Z0-9_./~${}:-]+$"),
+                                    symlink_entry_schema(),
                                 )])),
                                 properties: None,
                                 required: None,
@@ -203,6 +390,12 @@ pub fn to_writer<W: std::io::Write>(writer: W) -> serde_json::Result<()> {
                             None,
                         ),
                     ),
+                    (
+                        String::from("when"),
+                        condition_schema(
+                            "Only create the symlinks of this file if every given field matches",
+                        ),
+                    ),
                     (
                         String::from("$schema"),
                         JsonSchema::new(
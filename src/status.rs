@@ -0,0 +1,204 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{
+    config::{Provenance, SymlinkDeclaration},
+    lock::LockFile,
+    log::Logger,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkState {
+    /// The symlink points to the expected target, which exists
+    Ok,
+    /// Nothing exists at the symlink path
+    Missing,
+    /// The symlink points somewhere else
+    WrongTarget,
+    /// The symlink points to the expected target, which does not exist
+    DanglingTarget,
+    /// A real file or directory is in the way
+    NotASymlink,
+    /// In the lock file but not in config, `create links --prune` deletes it
+    InLockOnly,
+    /// In config but not in the lock file, `create links` creates it
+    InConfigOnly,
+}
+
+impl Display for LinkState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            LinkState::Ok => "ok",
+            LinkState::Missing => "missing",
+            LinkState::WrongTarget => "wrong target",
+            LinkState::DanglingTarget => "dangling target",
+            LinkState::NotASymlink => "not a symlink",
+            LinkState::InLockOnly => "in lock only",
+            LinkState::InConfigOnly => "in config only",
+        };
+        f.pad(name)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LinkStatus {
+    pub link_name: PathBuf,
+    pub state: LinkState,
+
+    /// Target from config, or from the lock file if no config files were given
+    pub expected_target: PathBuf,
+
+    /// Target of the symlink on disk, if it is a symlink
+    pub disk_target: Option<PathBuf>,
+
+    /// Config files the symlink comes from, if it is declared in config
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub declared_in: Option<Provenance>,
+}
+
+impl LinkStatus {
+    fn declared_in_suffix(&self) -> String {
+        self.declared_in
+            .as_ref()
+            .map_or(String::new(), |provenance| {
+                format!(" [{}]", provenance.config_file.to_string_lossy())
+            })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatusReport {
+    pub lock_file_path: PathBuf,
+    pub links: Vec<LinkStatus>,
+}
+
+impl StatusReport {
+    /// Compare the lock file, the config (if any) and the file system for
+    /// every symlink known to either the lock file or the config
+    pub fn new(
+        lock_file: &LockFile,
+        config_symlinks: Option<&HashMap<PathBuf, SymlinkDeclaration>>,
+    ) -> io::Result<Self> {
+        let mut link_names: BTreeSet<&PathBuf> = lock_file.symlinks().keys().collect();
+        if let Some(config_symlinks) = config_symlinks {
+            link_names.extend(config_symlinks.keys());
+        }
+
+        let mut links = Vec::new();
+        for link_name in link_names {
+            let lock_target = lock_file.target(link_name);
+            let declaration = config_symlinks.and_then(|symlinks| symlinks.get(link_name));
+            let config_target =
+                config_symlinks.map(|_| declaration.map(|declaration| &declaration.target));
+            let disk_target = if link_name.is_symlink() {
+                Some(link_name.read_link()?)
+            } else {
+                None
+            };
+
+            let (state, expected_target) = match (lock_target, config_target) {
+                (Some(lock_target), Some(None)) => (LinkState::InLockOnly, lock_target),
+                (None, Some(Some(config_target))) => (LinkState::InConfigOnly, config_target),
+                (_, Some(Some(config_target))) => {
+                    (disk_state(link_name, config_target)?, config_target)
+                }
+                (Some(lock_target), None) => (disk_state(link_name, lock_target)?, lock_target),
+                (None, None | Some(None)) => unreachable!("link name comes from lock or config"),
+            };
+
+            links.push(LinkStatus {
+                link_name: link_name.clone(),
+                state,
+                expected_target: expected_target.clone(),
+                disk_target,
+                declared_in: declaration.map(|declaration| declaration.provenance.clone()),
+            });
+        }
+
+        Ok(Self {
+            lock_file_path: lock_file.file_path().to_path_buf(),
+            links,
+        })
+    }
+
+    pub fn print(&self, log: &Logger) {
+        for link in &self.links {
+            match link.state {
+                LinkState::Ok => log.success(format_args!(
+                    "{:<15} {} -> {}",
+                    link.state,
+                    link.link_name.to_string_lossy(),
+                    link.expected_target.to_string_lossy()
+                )),
+                LinkState::InLockOnly | LinkState::InConfigOnly => log.info(format_args!(
+                    "{:<15} {} -> {}{}",
+                    link.state,
+                    link.link_name.to_string_lossy(),
+                    link.expected_target.to_string_lossy(),
+                    link.declared_in_suffix()
+                )),
+                LinkState::WrongTarget => log.error(format_args!(
+                    "{:<15} {} -> {} (expected {}){}",
+                    link.state,
+                    link.link_name.to_string_lossy(),
+                    link.disk_target
+                        .as_deref()
+                        .unwrap_or(Path::new("<NONE>"))
+                        .to_string_lossy(),
+                    link.expected_target.to_string_lossy(),
+                    link.declared_in_suffix()
+                )),
+                LinkState::Missing | LinkState::DanglingTarget | LinkState::NotASymlink => log
+                    .error(format_args!(
+                        "{:<15} {} -> {}{}",
+                        link.state,
+                        link.link_name.to_string_lossy(),
+                        link.expected_target.to_string_lossy(),
+                        link.declared_in_suffix()
+                    )),
+            }
+        }
+
+        let ok_count = self
+            .links
+            .iter()
+            .filter(|link| link.state == LinkState::Ok)
+            .count();
+        log.info(format_args!(
+            "status: {} of {} symlinks ok ({})",
+            ok_count,
+            self.links.len(),
+            self.lock_file_path.to_string_lossy()
+        ));
+    }
+}
+
+fn disk_state(link_name: &Path, expected_target: &Path) -> io::Result<LinkState> {
+    let symlink_metadata = match std::fs::symlink_metadata(link_name) {
+        Ok(symlink_metadata) => symlink_metadata,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(LinkState::Missing),
+        Err(error) => return Err(error),
+    };
+
+    if !symlink_metadata.is_symlink() {
+        return Ok(LinkState::NotASymlink);
+    }
+
+    let disk_target = crate::path::read_link_absolute(link_name)?;
+    if disk_target.as_path() != expected_target {
+        return Ok(LinkState::WrongTarget);
+    }
+
+    if !expected_target.exists() {
+        return Ok(LinkState::DanglingTarget);
+    }
+
+    Ok(LinkState::Ok)
+}
@@ -0,0 +1,126 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    lock::LockFile,
+    log::Logger,
+    output::{FailedLink, LinkEntry, UnlinkReport},
+};
+
+/// Symlinks of the lock file selected by `unlink`. Every given criterion must
+/// match, no criterion selects every symlink.
+#[derive(Debug, Default)]
+pub struct UnlinkFilter {
+    /// Absolute paths, a symlink matches if it is one of them or inside one
+    pub paths: Option<Vec<PathBuf>>,
+
+    /// Absolute symlink names declared in the selected config files
+    pub declared: Option<Vec<PathBuf>>,
+
+    /// Matched against absolute symlink names
+    pub pattern: Option<glob::Pattern>,
+}
+
+impl UnlinkFilter {
+    fn matches(&self, link_name: &Path) -> bool {
+        self.paths
+            .as_ref()
+            .is_none_or(|paths| paths.iter().any(|path| link_name.starts_with(path)))
+            && self
+                .declared
+                .as_ref()
+                .is_none_or(|declared| declared.iter().any(|name| name == link_name))
+            && self
+                .pattern
+                .as_ref()
+                .is_none_or(|pattern| pattern.matches_path(link_name))
+    }
+}
+
+/// Delete the selected symlinks and drop them from the lock file. A symlink
+/// is only deleted if it still points to the target recorded in the lock
+/// file, symlinks that no longer exist are dropped from the lock file.
+pub fn unlink(lock_file: &mut LockFile, filter: &UnlinkFilter) -> UnlinkReport {
+    let log = Logger::default();
+    let mut report = UnlinkReport::default();
+
+    let mut selected: Vec<_> = lock_file
+        .symlinks()
+        .iter()
+        .filter(|(link_name, _)| filter.matches(link_name))
+        .map(|(link_name, link)| (link_name.clone(), link.target.clone()))
+        .collect();
+    selected.sort();
+
+    if let Some(paths) = &filter.paths {
+        for path in paths {
+            if !selected
+                .iter()
+                .any(|(link_name, _)| link_name.starts_with(path))
+            {
+                log.error(format_args!(
+                    "unlink: {}: no symlink of the lock file matches",
+                    path.to_string_lossy()
+                ));
+                report.unmatched.push(path.clone());
+            }
+        }
+    }
+
+    for (link_name, link_target) in selected {
+        match unlink_symlink(link_name.as_path(), link_target.as_path()) {
+            Ok(true) => {
+                log.success(format_args!("unlink {}", link_name.to_string_lossy()));
+                lock_file.remove_symlink(link_name.as_path());
+                report.unlinked.push(LinkEntry::new(link_name, link_target));
+            }
+            Ok(false) => {
+                log.info(format_args!(
+                    "unlink: {} does not exist anymore",
+                    link_name.to_string_lossy()
+                ));
+                lock_file.remove_symlink(link_name.as_path());
+                report.missing.push(LinkEntry::new(link_name, link_target));
+            }
+            Err(error) => {
+                log.error(format_args!(
+                    "unlink: {}: {}",
+                    link_name.to_string_lossy(),
+                    error
+                ));
+                report.failed.push(FailedLink {
+                    link_name,
+                    error: error.to_string(),
+                });
+            }
+        }
+    }
+
+    report
+}
+
+/// Returns false if nothing exists at `link_name`
+fn unlink_symlink(link_name: &Path, link_target: &Path) -> io::Result<bool> {
+    match std::fs::symlink_metadata(link_name) {
+        Ok(metadata) if !metadata.is_symlink() => {
+            return Err(io::Error::other("not a symlink, refusing to delete it"))
+        }
+        Ok(_) => {}
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(error) => return Err(error),
+    }
+
+    let disk_target = crate::path::read_link_absolute(link_name)?;
+    if disk_target != link_target {
+        return Err(io::Error::other(format!(
+            "points to {} instead of {}, refusing to delete it",
+            disk_target.to_string_lossy(),
+            link_target.to_string_lossy()
+        )));
+    }
+
+    std::fs::remove_file(link_name)?;
+    Ok(true)
+}